@@ -12,3 +12,10 @@ for suit in ["spades", "hearts", "clubs", "diamonds"]:
         draw.text((0,0), "{}".format(card), color, font)
         del draw
         im.save("cards/card_{}_{}.png".format(card, suit), "PNG", dpi=(72,72))
+
+for color in ["black", "red"]:
+    im = Image.new("RGBA", (162, 252), "white")
+    draw = ImageDraw.Draw(im)
+    draw.text((0,0), "joker", color, font)
+    del draw
+    im.save("cards/card_joker_{}.png".format(color), "PNG", dpi=(72,72))
@@ -1,40 +1,57 @@
 //mod game;
-use rand;
-use rand::Rng;
 use game::cards::*;
-use game::solitaire::{StackId, CardGamePercept, Solitaire};
+use game::deal;
+use game::freecell::{FreeCell, FreeCellPercept};
+use game::spider::{Spider, SpiderAction, SpiderPercept};
+use game::pyramid::{Pyramid, PyramidAction, PyramidPercept};
+use game::sequence::{Sequence, SequenceAction, SequencePercept, Shape};
+use game::sequence;
+use game::history::History;
+use game::notation;
+use game::notation::Replay;
+use game::packed::PackedSolitaire;
+use game::rules::{Empty, Layout, Rules, Stock};
+use game::save::Save;
+use game::score;
+use game::score::{Clock, Score, Vegas};
+use game::solitaire::{StackId, CardGameAction, CardGamePercept, Solitaire};
+use game::solver;
+use game::solver::{Outcome, Report, Search};
 use game::problem::Problem;
-use std::char;
+use game::render::Renderable;
 use std;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-fn card_str(&(_, ref card): &(Ident, Option<Card>)) -> String {
+fn card_str((_, card): &(Ident, Option<Card>)) -> String {
     match card {
-        &Some(ref card) => {
+        Some(card) if card.is_joker() => String::from("Jk "),
+        Some(card) => {
             let suit = match card.suit {
-                0 => '♠',
-                1 => '♡',
-                2 => '♣',
-                3 => '♢',
-                _ => '?'
+                Suit::Spades => '♠',
+                Suit::Hearts => '♡',
+                Suit::Clubs => '♣',
+                Suit::Diamonds => '♢'
             };
-            let rank = match card.rank {
-                0 => String::from("A "),
-                10 => String::from("J "),
-                11 => String::from("Q "),
-                12 => String::from("K "),
-                x => format!("{: <2}",x+1)
-            };
-            format!("{}{}",suit, rank)
+            format!("{}{: <2}", suit, card.rank.name())
         },
         &None => String::from("###")
     }
 }
 
-fn print_percept(p: &CardGamePercept) {
+/// The score, move count and time taken so far.
+pub fn status_str(score: &Score, elapsed: Duration, won: bool) -> String {
+    format!("score {}   moves {}   time {}", score.total(elapsed, won), score.moves, score::clock_str(elapsed))
+}
+
+fn print_percept(p: &CardGamePercept, status: &str) {
+    println!("{}", status);
     let deck = p.stacks.get(&StackId(0,0)).unwrap();
     let runoff = p.stacks.get(&StackId(0,1)).unwrap();
 
-    if let Some(card) = deck.last() {
+    if !deck.is_empty() {
         print!("{} ", card_str(&(Ident::new(0),None)));
     } else {
         print!("    ");
@@ -66,70 +83,985 @@ fn print_percept(p: &CardGamePercept) {
                 print!("    ");
             }
         }
-        row = row+1;
+        row += 1;
         println!();
     }
 }
 
-pub fn deck() -> Vec<Card> {
-    let mut deck = Vec::new();
+fn print_freecell(p: &FreeCellPercept, status: &str) {
+    println!("{}", status);
     for i in 0..4 {
-        for j in 0..13 {
-            deck.push(Card::new(i, i%2, j));
+        match p.stacks.get(&StackId(0,i)).unwrap().last() {
+            Some(card) => print!("{} ", card_str(card)),
+            None => print!("    ")
+        }
+    }
+    for i in 0..4 {
+        match p.stacks.get(&StackId(2,i)).unwrap().last() {
+            Some(card) => print!(" {}", card_str(card)),
+            None => print!("    ")
+        }
+    }
+    println!();
+
+    let mut has = true;
+    let mut row = 0;
+    while has {
+        has = false;
+        for i in 0..8 {
+            if let Some(thing) = p.stacks.get(&StackId(1,i)).unwrap().get(row) {
+                print!("{} ", card_str(thing));
+                has = true;
+            } else {
+                print!("    ");
+            }
         }
+        row += 1;
+        println!();
     }
-    deck
 }
 
-pub fn deal_with_it() -> Solitaire {
-    let mut deck = Vec::new();
-    for i in 0..4 {
-        for j in 0..13 {
-            deck.push(Card::new(i, i%2, j));
+fn print_spider(p: &SpiderPercept, status: &str) {
+    println!("{}", status);
+    let stock = p.stacks.get(&StackId(0,0)).unwrap().len();
+    let done = (0..8).filter(|&i| !p.stacks.get(&StackId(2,i)).unwrap().is_empty()).count();
+    println!("{} deal{} left, {} run{} complete", stock/10, if stock/10 == 1 { "" } else { "s" }, done, if done == 1 { "" } else { "s" });
+
+    let mut has = true;
+    let mut row = 0;
+    while has {
+        has = false;
+        for i in 0..10 {
+            if let Some(thing) = p.stacks.get(&StackId(1,i)).unwrap().get(row) {
+                print!("{} ", card_str(thing));
+                has = true;
+            } else {
+                print!("    ");
+            }
+        }
+        row += 1;
+        println!();
+    }
+}
+
+fn print_pyramid(p: &PyramidPercept, status: &str) {
+    println!("{}", status);
+    for (r, row) in p.rows.iter().enumerate() {
+        print!("{}", " ".repeat((p.rows.len() - r - 1) * 2));
+        for slot in row {
+            match *slot {
+                Some((ref ident, ref card)) => print!("{} ", card_str(&(ident.clone(), Some(card.clone())))),
+                None => print!("    ")
+            }
         }
+        println!();
+    }
+    print!("stock {}  waste ", p.stock.len());
+    match p.waste.last() {
+        Some((ident, card)) => println!("{}", card_str(&(ident.clone(), Some(card.clone())))),
+        None => println!("-")
     }
-    {
-        let mut rng = rand::thread_rng();
-        let len = deck.len();
-        for i in 0..deck.len()-1 {
-            deck.swap(i, rng.gen_range(i,len));
+}
+
+/// Prints each card of the layout where the renderer would put it, two characters to a column.
+fn print_sequence(p: &SequencePercept, status: &str) {
+    println!("{}", status);
+    let mut lines: Vec<(i32, usize, String)> = Vec::new();
+    for (i, slot) in p.tableau.iter().enumerate() {
+        if let Some(slot) = slot {
+            let loc = p.shape.location(i);
+            lines.push((loc.y.0*2 + loc.y.1/2, loc.x.0 as usize*2, card_str(slot)));
         }
     }
-    Solitaire::deal(deck, 7,4)
+    lines.sort();
+    let mut line = String::new();
+    for (i, &(y, x, ref card)) in lines.iter().enumerate() {
+        let pad = x.saturating_sub(line.chars().count());
+        line = line + &" ".repeat(pad) + card;
+        if lines.get(i+1).is_none_or(|next| next.0 != y) {
+            println!("{}", line);
+            line.clear();
+        }
+    }
+    print!("stock {}  waste ", p.stock.len());
+    match p.waste.last() {
+        Some((ident, card)) => println!("{}", card_str(&(ident.clone(), Some(card.clone())))),
+        None => println!("-")
+    }
 }
 
-pub  fn cmd_solitare() {
-    let mut deck = Vec::new();
-    for i in 0..4 {
-        for j in 0..13 {
-            deck.push(Card::new(i, i%2, j));
+/// How `--batch` writes its results.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Format {
+    Text,
+    Csv,
+    Json
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Game {
+    Klondike,
+    FreeCell,
+    Spider,
+    Pyramid,
+    Golf,
+    TriPeaks
+}
+
+pub struct Options {
+    pub game: Game,
+    /// How many suits a game of spider is played with.
+    pub suits: usize,
+    pub seed: u64,
+    pub text: bool,
+    pub solve: bool,
+    /// How many deals to time the solver on, from `seed` on.
+    pub bench: Option<usize>,
+    /// How many deals to find the winnable share of, from `seed` on.
+    pub batch: Option<usize>,
+    pub format: Format,
+    pub budget: usize,
+    /// How long the solver may spend on a deal in a batch.
+    pub time: Option<Duration>,
+    /// How many threads the solver runs on.
+    pub threads: usize,
+    pub rules: Rules,
+    pub vegas: bool,
+    /// A saved game to resume instead of dealing a new one.
+    pub load: Option<PathBuf>,
+    /// Set when `load` was picked automatically rather than asked for.
+    pub resume: bool,
+    /// A replay to play back.
+    pub replay: Option<PathBuf>,
+    /// A replay to check without playing it back.
+    pub verify: Option<PathBuf>,
+    /// Where to write a replay of the game when it ends.
+    pub record: Option<PathBuf>
+}
+
+impl Options {
+    pub fn from_args() -> Result<Options, String> {
+        let mut opts = Options {
+            game: Game::Klondike,
+            suits: 1,
+            seed: deal::random_seed(),
+            text: false,
+            solve: false,
+            bench: None,
+            batch: None,
+            format: Format::Text,
+            budget: 1000000,
+            time: None,
+            threads: 1,
+            rules: Rules::default(),
+            vegas: false,
+            load: None,
+            resume: false,
+            replay: None,
+            verify: None,
+            record: None
+        };
+        let mut fresh = false;
+        let mut seeded = false;
+        // the draw and pass limit apply to whichever game is picked, in any order
+        let mut preset = None;
+        let mut draw = None;
+        let mut passes = None;
+        let mut empty = None;
+        let mut jokers = None;
+        let mut suits = false;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--game" => {
+                    let val = args.next().ok_or("--game needs a game name".to_string())?;
+                    opts.game = match val.as_str() {
+                        "freecell" => Game::FreeCell,
+                        "spider" => Game::Spider,
+                        "pyramid" => Game::Pyramid,
+                        "golf" => Game::Golf,
+                        "tripeaks" => Game::TriPeaks,
+                        // the rest of the klondike family only differs in its rules
+                        name => match Rules::named(name) {
+                            Some(rules) => {
+                                preset = Some(rules);
+                                Game::Klondike
+                            },
+                            None => return Err(format!("unknown game '{}'", val))
+                        }
+                    };
+                },
+                "--suits" => {
+                    let val = args.next().ok_or("--suits needs a suit count".to_string())?;
+                    opts.suits = match val.parse() {
+                        Ok(x) if x == 1 || x == 2 || x == 4 => x,
+                        _ => return Err(format!("invalid suit count '{}', spider is played with 1, 2 or 4", val))
+                    };
+                    suits = true;
+                },
+                "--text" => opts.text = true,
+                "--solve" => opts.solve = true,
+                "--bench" => {
+                    let val = args.next().ok_or("--bench needs a deal count".to_string())?;
+                    opts.bench = match val.parse() {
+                        Ok(x) if x > 0 => Some(x),
+                        _ => return Err(format!("invalid deal count '{}'", val))
+                    };
+                },
+                "--batch" => {
+                    let val = args.next().ok_or("--batch needs a deal count".to_string())?;
+                    opts.batch = match val.parse() {
+                        Ok(x) if x > 0 => Some(x),
+                        _ => return Err(format!("invalid deal count '{}'", val))
+                    };
+                },
+                "--format" => {
+                    let val = args.next().ok_or("--format needs 'text', 'csv' or 'json'".to_string())?;
+                    opts.format = match val.as_str() {
+                        "text" => Format::Text,
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => return Err(format!("invalid format '{}', expected 'text', 'csv' or 'json'", val))
+                    };
+                },
+                "--time" => {
+                    let val = args.next().ok_or("--time needs a number of seconds".to_string())?;
+                    opts.time = match val.parse::<f64>() {
+                        Ok(x) if x > 0.0 => Some(Duration::new(x as u64, (x.fract() * 1e9) as u32)),
+                        _ => return Err(format!("invalid number of seconds '{}'", val))
+                    };
+                },
+                "--threads" => {
+                    let val = args.next().ok_or("--threads needs a thread count".to_string())?;
+                    opts.threads = match val.parse() {
+                        Ok(x) if x > 0 => x,
+                        _ => return Err(format!("invalid thread count '{}'", val))
+                    };
+                },
+                "--draw" => {
+                    let val = args.next().ok_or("--draw needs a card count".to_string())?;
+                    draw = match val.parse() {
+                        Ok(x) if x > 0 => Some(x),
+                        _ => return Err(format!("invalid card count '{}'", val))
+                    };
+                },
+                "--passes" => {
+                    let val = args.next().ok_or("--passes needs a count".to_string())?;
+                    passes = match val.parse() {
+                        Ok(x) if x > 0 => Some(x),
+                        _ => return Err(format!("invalid pass count '{}'", val))
+                    };
+                },
+                "--empty" => {
+                    let val = args.next().ok_or("--empty needs 'kings' or 'any'".to_string())?;
+                    empty = match val.as_str() {
+                        "kings" => Some(Empty::Kings),
+                        "any" => Some(Empty::AnyCard),
+                        _ => return Err(format!("invalid empty row rule '{}', expected 'kings' or 'any'", val))
+                    };
+                },
+                "--jokers" => {
+                    let val = args.next().ok_or("--jokers needs a count".to_string())?;
+                    jokers = Some(val.parse().map_err(|_| format!("invalid joker count '{}'", val))?);
+                },
+                "--vegas" => opts.vegas = true,
+                "--budget" => {
+                    let val = args.next().ok_or("--budget needs a node count".to_string())?;
+                    opts.budget = val.parse().map_err(|_| format!("invalid node count '{}'", val))?;
+                },
+                "--seed" => {
+                    let val = args.next().ok_or("--seed needs a deal number".to_string())?;
+                    opts.seed = val.parse().map_err(|_| format!("invalid deal number '{}'", val))?;
+                    fresh = true;
+                    seeded = true;
+                },
+                "--new" => fresh = true,
+                "--load" => {
+                    let val = args.next().ok_or("--load needs a file name".to_string())?;
+                    opts.load = Some(PathBuf::from(val));
+                },
+                "--replay" | "--verify" | "--record" => {
+                    let val = args.next().ok_or(format!("{} needs a file name", arg))?;
+                    let path = Some(PathBuf::from(val));
+                    match arg.as_str() {
+                        "--replay" => opts.replay = path,
+                        "--verify" => opts.verify = path,
+                        _ => opts.record = path
+                    }
+                    fresh = true;
+                },
+                x => return Err(format!("unrecognized argument '{}'", x))
+            }
+        }
+        // saves and replays are played under their own rules, so asking for others means a new deal
+        let ruled = preset.is_some() || draw.is_some() || passes.is_some() || empty.is_some() || jokers.is_some() || opts.vegas;
+        if ruled && (opts.load.is_some() || opts.replay.is_some() || opts.verify.is_some()) {
+            return Err("--game, --draw, --passes, --empty, --jokers and --vegas can't be used with --load, --replay or --verify, which keep the rules they were played under".to_string());
+        }
+        if let Some(rules) = preset {
+            opts.rules = rules;
+        }
+        if let Some(draw) = draw {
+            opts.rules.draw = draw;
+        }
+        if passes.is_some() {
+            opts.rules.passes = passes;
+        }
+        if let Some(empty) = empty {
+            opts.rules.empty = empty;
+        }
+        if let Some(jokers) = jokers {
+            opts.rules.jokers = jokers;
+        }
+        if suits && opts.game != Game::Spider {
+            return Err("--suits only applies to spider".to_string());
+        }
+        if opts.game != Game::Klondike {
+            if draw.is_some() || empty.is_some() || jokers.is_some() {
+                return Err("--draw, --empty and --jokers only apply to the klondike family".to_string());
+            }
+            if passes.is_some() && opts.game != Game::Pyramid {
+                return Err("--passes only applies to the klondike family and pyramid".to_string());
+            }
+            if opts.vegas || opts.load.is_some() || opts.replay.is_some() || opts.record.is_some() || opts.bench.is_some() || opts.batch.is_some() {
+                return Err("saves, replays, benchmarks, batches and vegas scoring are only available in the klondike family".to_string());
+            }
+            if opts.game == Game::FreeCell {
+                // stick to the deals Microsoft FreeCell numbers
+                if !seeded {
+                    opts.seed = opts.seed % 32000 + 1;
+                } else if opts.seed < 1 || opts.seed > 32000 {
+                    return Err(format!("invalid deal number '{}', freecell deals are numbered 1 to 32000", opts.seed));
+                }
+            }
+            return Ok(opts);
+        }
+        if opts.load.is_none() && !fresh && !ruled && !opts.solve && opts.bench.is_none() && opts.batch.is_none() {
+            let path = data_path("save");
+            if path.exists() {
+                opts.load = Some(path);
+                opts.resume = true;
+            }
+        }
+        if opts.vegas && opts.rules.passes.is_none() {
+            opts.rules.passes = Some(if opts.rules.draw == 1 { 1 } else { 3 });
+        }
+        Ok(opts)
+    }
+    /// A one-line summary of the deal and rules, for reporting or replaying a game.
+    pub fn describe(&self) -> String {
+        match self.game {
+            Game::FreeCell => return format!("freecell deal #{}", self.seed),
+            Game::Spider => return format!("spider deal #{}, {} suit{}", self.seed, self.suits, if self.suits == 1 { "" } else { "s" }),
+            Game::Pyramid => {
+                let mut out = format!("pyramid deal #{}", self.seed);
+                if let Some(passes) = self.rules.passes {
+                    out = out + &format!(", {} pass{}", passes, if passes == 1 { "" } else { "es" });
+                }
+                return out;
+            },
+            Game::Golf => return format!("golf deal #{}", self.seed),
+            Game::TriPeaks => return format!("tripeaks deal #{}", self.seed),
+            Game::Klondike => ()
+        }
+        let mut out = match self.rules.name() {
+            Some("klondike") => format!("deal #{}", self.seed),
+            Some(name) => format!("{} deal #{}", name, self.seed),
+            None => format!("custom deal #{}", self.seed)
+        };
+        // yukon deals every card, leaving no deck to draw from, and a deck dealt onto the rows
+        // is only dealt once
+        if self.rules.layout != Layout::Yukon && self.rules.stock == Stock::Runoff {
+            out = out + &format!(", draw {}", self.rules.draw);
+            if let Some(passes) = self.rules.passes {
+                out = out + &format!(", {} pass{}", passes, if passes == 1 { "" } else { "es" });
+            }
+        }
+        if self.rules.house_empty() {
+            out += match self.rules.empty {
+                Empty::AnyCard => ", any card in empty rows",
+                Empty::Kings => ", kings only in empty rows"
+            };
+        }
+        if self.rules.jokers > 0 {
+            out = out + &format!(", {} joker{}", self.rules.jokers, if self.rules.jokers == 1 { "" } else { "s" });
+        }
+        if self.vegas {
+            out += ", vegas";
+        }
+        out
+    }
+    /// The layout for a game of golf or tripeaks.
+    pub fn shape(&self) -> Shape {
+        match self.game {
+            Game::Golf => Shape::Golf,
+            _ => Shape::TriPeaks
+        }
+    }
+}
+
+pub fn usage() -> &'static str {
+    "usage: solitaire [--game <game>] [--text | --solve | --bench <deals> | --batch <deals>] [--new | --seed <deal number> | --load <file> | --replay <file> | --verify <file>]\n                 [--draw <cards>] [--passes <count>] [--empty kings|any] [--jokers <count>] [--vegas] [--suits <count>] [--budget <nodes>] [--time <seconds>] [--threads <count>] [--format text|csv|json] [--record <file>]\ngames: klondike thoughtful whitehead agnes easthaven yukon russian freecell spider pyramid golf tripeaks"
+}
+
+/// Where files that outlive a single game are kept.
+pub fn data_path(name: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or(PathBuf::from("."));
+    home.join(".solitaire").join(name)
+}
+
+fn money(x: i64) -> String {
+    if x < 0 {
+        format!("-${}", -x)
+    } else {
+        format!("${}", x)
+    }
+}
+
+/// A game the window and the terminal know how to play. Every game addresses its cards by
+/// stack and position, so beyond the rules themselves only starting, scoring, showing and
+/// finishing a game, and writing down its moves, differ between them.
+pub trait Table: Sized + Clone + 'static {
+    type Action: Clone + PartialEq + Debug;
+    type Percept: Renderable<CardId=(StackId, Option<usize>), Action=Self::Action>;
+    /// Deals or resumes the game `opts` describes. The options are updated to describe the game
+    /// actually being played.
+    fn start(opts: &mut Options) -> Result<Session<Self>, String>;
+    /// The score and time so far, for printing after every move.
+    fn status(session: &Session<Self>) -> String;
+    /// Prints the layout for playing in the terminal.
+    fn show(&self, status: &str);
+    /// Called when the player quits or wins.
+    fn finish(session: &mut Session<Self>, opts: &Options);
+    fn format_move(act: &Self::Action) -> String;
+    /// Reads a move typed in the terminal. Only legal moves are accepted.
+    fn parse_move(text: &str, state: &Self) -> Result<Self::Action, String>;
+    /// Whether `act` is one the automatic play to the foundations should make.
+    fn plays_home(act: &Self::Action) -> bool;
+}
+
+/// A game being played, whether freshly dealt or resumed from a save.
+pub struct Session<S: Table> {
+    pub history: History<S, S::Action>,
+    pub clock: Clock,
+    pub vegas: Option<Vegas>,
+    /// Whether an unfinished game is saved when the player quits.
+    pub keep: bool,
+    /// Moves waiting to be played back, from a replay or the solver.
+    pub queue: VecDeque<S::Action>
+}
+
+impl <S: Table> Session<S> {
+    pub fn new(state: S, keep: bool) -> Session<S> {
+        Session {
+            history: History::new(state),
+            clock: Clock::new(Duration::from_secs(0)),
+            vegas: None,
+            keep,
+            queue: VecDeque::new()
+        }
+    }
+    pub fn start(opts: &mut Options) -> Result<Session<S>, String> {
+        S::start(opts)
+    }
+    pub fn status(&self) -> String {
+        S::status(self)
+    }
+    pub fn finish(&mut self, opts: &Options) {
+        S::finish(self, opts)
+    }
+}
+
+impl Session<Solitaire> {
+    /// Resumes the game `opts` asks to load, or deals a new one. A broken save is only an error
+    /// when it was asked for explicitly; the automatic save is skipped with a warning instead.
+    fn resume(opts: &mut Options) -> Result<Session<Solitaire>, String> {
+        if let Some(path) = opts.load.clone() {
+            match Save::read(&path) {
+                Ok(save) => {
+                    println!("resuming the game saved in {}", path.display());
+                    opts.seed = save.seed;
+                    opts.rules = save.history.start().rules().clone();
+                    opts.vegas = save.vegas.is_some();
+                    let vegas = match save.vegas {
+                        Some(paid) => match Vegas::load(data_path("vegas"), paid) {
+                            Ok(vegas) => Some(vegas),
+                            Err(e) => {
+                                println!("vegas: could not load the bank: {}", e);
+                                None
+                            }
+                        },
+                        None => None
+                    };
+                    return Ok(Session {
+                        history: save.history,
+                        clock: Clock::new(save.elapsed),
+                        vegas,
+                        keep: true,
+                        queue: VecDeque::new()
+                    });
+                },
+                Err(e) => {
+                    if !opts.resume {
+                        return Err(e);
+                    }
+                    println!("{}, dealing a new game", e);
+                }
+            }
+        }
+        let mut session = Session::new(deal_with_it(opts.seed, &opts.rules), true);
+        if opts.vegas {
+            match Vegas::ante(data_path("vegas")) {
+                Ok(vegas) => {
+                    println!("vegas: paid {} to deal, bank is now {}", money(score::VEGAS_ANTE), money(vegas.bank));
+                    session.vegas = Some(vegas);
+                },
+                Err(e) => println!("vegas: could not load the bank: {}", e)
+            }
+        }
+        Ok(session)
+    }
+    /// Sets up the deal of a replay with its moves queued up to be played back, and the options
+    /// updated to match. Replays are never saved.
+    pub fn replay(opts: &mut Options, replay: &Replay) -> Session<Solitaire> {
+        opts.seed = replay.seed;
+        opts.rules = replay.rules.clone();
+        opts.vegas = false;
+        let mut session = Session::new(replay.start(), false);
+        session.queue.extend(replay.actions.iter().cloned());
+        session
+    }
+}
+
+impl Table for Solitaire {
+    type Action = CardGameAction;
+    type Percept = CardGamePercept;
+    fn start(opts: &mut Options) -> Result<Session<Solitaire>, String> {
+        match opts.replay.clone() {
+            Some(path) => Replay::read(path).map(|replay| Session::replay(opts, &replay)),
+            None => Session::resume(opts)
+        }
+    }
+    fn status(session: &Session<Solitaire>) -> String {
+        status_str(&Score::tally(&session.history), session.clock.elapsed(), session.history.current().is_goal())
+    }
+    fn show(&self, status: &str) {
+        print_percept(&self.percept(), status);
+    }
+    fn format_move(act: &CardGameAction) -> String {
+        notation::format_move(act)
+    }
+    fn parse_move(text: &str, state: &Solitaire) -> Result<CardGameAction, String> {
+        notation::parse_move(text, state)
+    }
+    fn plays_home(act: &CardGameAction) -> bool {
+        matches!(act, &CardGameAction::Move(_, StackId(2, _)))
+    }
+    /// Unfinished games are saved so they can be resumed next time; finished ones clear the save.
+    fn finish(session: &mut Session<Solitaire>, opts: &Options) {
+        if let Some(ref mut vegas) = session.vegas {
+            match vegas.settle(session.history.current()) {
+                Ok(won) => println!("vegas: {} this game, bank is now {}", money(won), money(vegas.bank)),
+                Err(e) => println!("vegas: could not save the bank: {}", e)
+            }
+        }
+        if let Some(ref path) = opts.record {
+            let replay = Replay {
+                seed: opts.seed,
+                rules: opts.rules.clone(),
+                actions: session.history.actions().into_iter().cloned().collect()
+            };
+            match replay.write(path) {
+                Ok(()) => println!("replay written to {}", path.display()),
+                Err(e) => println!("could not write a replay to {}: {}", path.display(), e)
+            }
+        }
+        if !session.keep {
+            return;
+        }
+        let path = data_path("save");
+        if session.history.current().is_goal() {
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    println!("could not remove {}: {}", path.display(), e);
+                }
+            }
+            return;
+        }
+        let save = Save {
+            seed: opts.seed,
+            vegas: session.vegas.as_ref().map(|x| x.paid),
+            elapsed: session.clock.elapsed(),
+            history: session.history.clone()
+        };
+        match save.write(&path) {
+            Ok(()) => println!("game saved to {}", path.display()),
+            Err(e) => println!("could not save the game to {}: {}", path.display(), e)
+        }
+    }
+}
+
+impl Table for FreeCell {
+    type Action = CardGameAction;
+    type Percept = FreeCellPercept;
+    fn start(opts: &mut Options) -> Result<Session<FreeCell>, String> {
+        Ok(Session::new(FreeCell::deal_number(opts.seed), false))
+    }
+    fn status(session: &Session<FreeCell>) -> String {
+        moves_str(session)
+    }
+    fn show(&self, status: &str) {
+        print_freecell(&self.percept(), status);
+    }
+    fn finish(_: &mut Session<FreeCell>, _: &Options) {
+    }
+    fn format_move(act: &CardGameAction) -> String {
+        notation::format_move(act)
+    }
+    fn parse_move(text: &str, state: &FreeCell) -> Result<CardGameAction, String> {
+        notation::parse_move(text, state)
+    }
+    fn plays_home(act: &CardGameAction) -> bool {
+        Solitaire::plays_home(act)
+    }
+}
+
+impl Table for Spider {
+    type Action = SpiderAction;
+    type Percept = SpiderPercept;
+    fn start(opts: &mut Options) -> Result<Session<Spider>, String> {
+        Ok(Session::new(Spider::deal_number(opts.seed, opts.suits), false))
+    }
+    /// Scored the way Windows scores it: 500 to start, less one per move and plus 100 for
+    /// every completed run.
+    fn status(session: &Session<Spider>) -> String {
+        let moves = session.history.actions().len();
+        let points = 500 + 100 * session.history.current().completed() as i64 - moves as i64;
+        format!("score {}   {}", points, moves_str(session))
+    }
+    fn show(&self, status: &str) {
+        print_spider(&self.percept(), status);
+    }
+    fn finish(_: &mut Session<Spider>, _: &Options) {
+    }
+    fn format_move(act: &SpiderAction) -> String {
+        notation::format_column_move(act)
+    }
+    fn parse_move(text: &str, state: &Spider) -> Result<SpiderAction, String> {
+        notation::parse_column_move(text, state)
+    }
+    /// Completed runs go to the foundations by themselves.
+    fn plays_home(_: &SpiderAction) -> bool {
+        false
+    }
+}
+
+impl Table for Pyramid {
+    type Action = PyramidAction;
+    type Percept = PyramidPercept;
+    fn start(opts: &mut Options) -> Result<Session<Pyramid>, String> {
+        Ok(Session::new(Pyramid::deal_number(opts.seed, opts.rules.passes), false))
+    }
+    fn status(session: &Session<Pyramid>) -> String {
+        moves_str(session)
+    }
+    fn show(&self, status: &str) {
+        print_pyramid(&self.percept(), status);
+    }
+    fn finish(_: &mut Session<Pyramid>, _: &Options) {
+    }
+    fn format_move(act: &PyramidAction) -> String {
+        notation::format_pair_move(act)
+    }
+    fn parse_move(text: &str, state: &Pyramid) -> Result<PyramidAction, String> {
+        notation::parse_pair_move(text, state)
+    }
+    /// Kings can always be cleared straight away.
+    fn plays_home(act: &PyramidAction) -> bool {
+        matches!(act, &PyramidAction::King(_))
+    }
+}
+
+impl Table for Sequence {
+    type Action = SequenceAction;
+    type Percept = SequencePercept;
+    fn start(opts: &mut Options) -> Result<Session<Sequence>, String> {
+        Ok(Session::new(Sequence::deal_number(opts.seed, opts.shape()), false))
+    }
+    /// Golf is scored like the real thing, by the cards left over; TriPeaks by streaks.
+    fn status(session: &Session<Sequence>) -> String {
+        match session.history.current().shape() {
+            Shape::Golf => format!("cards left {}   {}", session.history.current().left(), moves_str(session)),
+            Shape::TriPeaks => {
+                let (points, streak) = sequence::streak_score(&session.history);
+                format!("score {}   streak {}   {}", points, streak, moves_str(session))
+            }
+        }
+    }
+    fn show(&self, status: &str) {
+        print_sequence(&self.percept(), status);
+    }
+    fn finish(_: &mut Session<Sequence>, _: &Options) {
+    }
+    fn format_move(act: &SequenceAction) -> String {
+        notation::format_play_move(act)
+    }
+    fn parse_move(text: &str, state: &Sequence) -> Result<SequenceAction, String> {
+        notation::parse_play_move(text, state)
+    }
+    /// Everything goes to the waste, and which card to play there is the whole game.
+    fn plays_home(_: &SequenceAction) -> bool {
+        false
+    }
+}
+
+/// The move count and time, for games without a score.
+fn moves_str<S: Table>(session: &Session<S>) -> String {
+    format!("moves {}   time {}", session.history.actions().len(), score::clock_str(session.clock.elapsed()))
+}
+
+pub fn deal_with_it(seed: u64, rules: &Rules) -> Solitaire {
+    Solitaire::deal_number(seed, rules.clone())
+}
+
+pub fn print_report<S: Table>(report: &Report<S::Action>) {
+    match report.outcome {
+        Outcome::Solved(ref acts) => {
+            println!("solved in {} moves ({} positions searched):", acts.len(), report.nodes);
+            let moves: Vec<String> = acts.iter().map(S::format_move).collect();
+            for line in moves.chunks(10) {
+                println!("{}", line.join(" "));
+            }
+        },
+        Outcome::Unwinnable => println!("no solution exists ({} positions searched)", report.nodes),
+        Outcome::Unknown => println!("gave up after searching {} positions", report.nodes)
+    }
+}
+
+fn solve_deal<S>(opts: &Options, state: &S) where S: Table + Problem<<S as Table>::Action, <S as Table>::Percept> + Search<<S as Table>::Action> + Send,
+    <S as Table>::Action: Send, <S as Search<<S as Table>::Action>>::Key: Send {
+    println!("{}", opts.describe());
+    print_report::<S>(&solver::solve_parallel(state, opts.budget, None, opts.threads));
+}
+
+pub fn cmd_solve(opts: &Options) {
+    match opts.game {
+        Game::FreeCell => return solve_deal(opts, &FreeCell::deal_number(opts.seed)),
+        Game::Spider => return solve_deal(opts, &Spider::deal_number(opts.seed, opts.suits)),
+        Game::Pyramid => return solve_deal(opts, &Pyramid::deal_number(opts.seed, opts.rules.passes)),
+        Game::Golf | Game::TriPeaks => return solve_deal(opts, &Sequence::deal_number(opts.seed, opts.shape())),
+        Game::Klondike => ()
+    }
+    let state = match opts.load {
+        Some(ref path) => match Save::read(path) {
+            Ok(save) => {
+                println!("solving the game saved in {}", path.display());
+                save.history.current().clone()
+            },
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        },
+        None => {
+            println!("{}", opts.describe());
+            deal_with_it(opts.seed, &opts.rules)
+        }
+    };
+    print_report::<Solitaire>(&solver::solve_parallel(&state, opts.budget, None, opts.threads));
+}
+
+fn seconds(time: Duration) -> f64 {
+    time.as_secs() as f64 + time.subsec_nanos() as f64 / 1e9
+}
+
+/// Solves a run of deals twice, once as they are played and once packed, and compares how long
+/// each takes. Both should search the same positions and come to the same answers.
+pub fn cmd_bench(opts: &Options, count: usize) {
+    println!("{}, and the {} after it", opts.describe(), count - 1);
+    let mut nodes = 0;
+    let mut plain = Duration::new(0, 0);
+    let mut packed = Duration::new(0, 0);
+    for seed in opts.seed..opts.seed + count as u64 {
+        let state = deal_with_it(seed, &opts.rules);
+        let small = match PackedSolitaire::pack(&state) {
+            Ok(small) => small,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        let started = Instant::now();
+        let report = solver::solve(&state, opts.budget);
+        let took = started.elapsed();
+        let started = Instant::now();
+        let small_report = solver::solve(&small, opts.budget);
+        let small_took = started.elapsed();
+        println!("deal #{}: {} positions, {:.3}s plain, {:.3}s packed{}", seed, report.nodes, seconds(took), seconds(small_took),
+            if report.outcome == small_report.outcome && report.nodes == small_report.nodes { "" } else { ", RESULTS DIFFER" });
+        nodes += report.nodes;
+        plain += took;
+        packed += small_took;
+    }
+    println!("plain:  {:.0} positions a second", nodes as f64 / seconds(plain));
+    println!("packed: {:.0} positions a second, {:.1} times as fast", nodes as f64 / seconds(packed), seconds(plain) / seconds(packed));
+}
+
+/// Solves a run of deals, from the chosen one on, and reports how many could be won.
+pub fn cmd_batch(opts: &Options, count: usize) {
+    let mut results = Vec::new();
+    let (mut won, mut lost, mut moves, mut nodes) = (0, 0, 0, 0);
+    for seed in opts.seed..opts.seed + count as u64 {
+        let started = Instant::now();
+        let report = solver::solve_parallel(&deal_with_it(seed, &opts.rules), opts.budget, opts.time, opts.threads);
+        let took = seconds(started.elapsed());
+        let result = match report.outcome {
+            Outcome::Solved(ref acts) => {
+                won += 1;
+                moves += acts.len();
+                "won"
+            },
+            Outcome::Unwinnable => {
+                lost += 1;
+                "lost"
+            },
+            Outcome::Unknown => "unknown"
+        };
+        let length = match report.outcome {
+            Outcome::Solved(ref acts) => Some(acts.len()),
+            _ => None
+        };
+        if opts.format == Format::Text {
+            match length {
+                Some(length) => println!("deal #{}: won in {} moves ({} positions, {:.3}s)", seed, length, report.nodes, took),
+                None => println!("deal #{}: {} ({} positions, {:.3}s)", seed, result, report.nodes, took)
+            }
+        }
+        nodes += report.nodes;
+        results.push((seed, result, length, report.nodes, took));
+    }
+    let unknown = count - won - lost;
+    // solutions are cut down to size, but aren't the shortest there could be
+    let average_moves = if won > 0 { Some(moves as f64 / won as f64) } else { None };
+    let average_nodes = nodes as f64 / count as f64;
+    let name = opts.rules.name().unwrap_or("custom");
+    match opts.format {
+        Format::Text => {
+            println!("{} deals from #{}: {} won ({:.1}%), {} lost, {} unknown", count, opts.seed, won, 100.0 * won as f64 / count as f64, lost, unknown);
+            match average_moves {
+                Some(moves) => println!("solutions found average {:.1} moves, {:.0} positions searched per deal", moves, average_nodes),
+                None => println!("no solutions found, {:.0} positions searched per deal", average_nodes)
+            }
+        },
+        Format::Csv => {
+            println!("game,rules,first,deals,won,lost,unknown,average solution moves,average positions");
+            println!("{},{},{},{},{},{},{},{},{:.1}", name, opts.rules.to_text(), opts.seed, count, won, lost, unknown,
+                average_moves.map_or(String::new(), |x| format!("{:.1}", x)), average_nodes);
+        },
+        Format::Json => {
+            let deals: Vec<String> = results.iter().map(|&(seed, result, length, nodes, took)| {
+                format!("{{\"seed\": {}, \"result\": \"{}\", \"moves\": {}, \"positions\": {}, \"seconds\": {:.3}}}",
+                    seed, result, length.map_or(String::from("null"), |x| x.to_string()), nodes, took)
+            }).collect();
+            println!("{{\"game\": \"{}\", \"rules\": \"{}\", \"first\": {}, \"deals\": {}, \"won\": {}, \"lost\": {}, \"unknown\": {}, \"average_solution_moves\": {}, \"average_positions\": {:.1}, \"results\": [{}]}}",
+                name, opts.rules.to_text(), opts.seed, count, won, lost, unknown, average_moves.map_or(String::from("null"), |x| format!("{:.1}", x)), average_nodes, deals.join(", "));
         }
     }
-    {
-        let mut rng = rand::thread_rng();
-        let len = deck.len();
-        for i in 0..deck.len()-1 {
-            deck.swap(i, rng.gen_range(i,len));
+}
+
+pub fn cmd_verify(path: &PathBuf) -> bool {
+    match Replay::read(path) {
+        Ok(replay) => {
+            let end = replay.play();
+            println!("deal #{}: {} legal moves, {}", replay.seed, replay.actions.len(), if end.is_goal() { "solved" } else { "not solved" });
+            true
+        },
+        Err(e) => {
+            println!("{}", e);
+            false
         }
     }
-    println!("{:?}", deck);
-    let mut stdin = std::io::stdin();
-    let mut sol = Solitaire::deal(deck, 7,4);
-    loop {
-        print_percept(&sol.percept());
-        println!("select an action:");
-        let actions = sol.actions();
+}
+
+pub  fn cmd_solitare(opts: &mut Options) {
+    match opts.game {
+        Game::Klondike => play::<Solitaire>(opts),
+        Game::FreeCell => play::<FreeCell>(opts),
+        Game::Spider => play::<Spider>(opts),
+        Game::Pyramid => play::<Pyramid>(opts),
+        Game::Golf | Game::TriPeaks => play::<Sequence>(opts)
+    }
+}
+
+fn play<S>(opts: &mut Options) where S: Table + Problem<<S as Table>::Action, <S as Table>::Percept> + Search<<S as Table>::Action> {
+    let mut session = match Session::<S>::start(opts) {
+        Ok(session) => session,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    println!("{}", opts.describe());
+    let stdin = std::io::stdin();
+    while !session.history.current().is_goal() {
+        session.history.current().show(&session.status());
+        println!("select an action (u to undo, r to redo, s to solve, q to quit):");
+        let actions = session.history.current().actions();
         for (i,act) in actions.iter().enumerate() {
-            println!("{}{:?}", i, act);
+            println!("{} {}", i, S::format_move(act));
         }
         let mut act = None;
-        while act == None {
+        while act.is_none() {
             let mut thing = String::new();
-            stdin.read_line(&mut thing).unwrap();
-            let selection: usize = thing.trim().parse().unwrap();
-            act = actions.get(selection);
+            if stdin.read_line(&mut thing).unwrap() == 0 {
+                thing = String::from("q");
+            }
+            match thing.trim() {
+                "q" | "quit" => {
+                    session.finish(opts);
+                    return;
+                },
+                "u" | "undo" => {
+                    if !session.history.undo() {
+                        println!("nothing to undo");
+                    }
+                    break;
+                },
+                "r" | "redo" => {
+                    if !session.history.redo() {
+                        println!("nothing to redo");
+                    }
+                    break;
+                },
+                "s" | "solve" => {
+                    print_report::<S>(&solver::solve(session.history.current(), opts.budget));
+                },
+                x => {
+                    act = match x.parse::<usize>() {
+                        Ok(selection) => match actions.get(selection) {
+                            Some(act) => Some(act.clone()),
+                            None => {
+                                println!("there is no move {}", selection);
+                                None
+                            }
+                        },
+                        Err(_) => match S::parse_move(x, session.history.current()) {
+                            Ok(act) => Some(act),
+                            Err(e) => {
+                                println!("{}", e);
+                                None
+                            }
+                        }
+                    };
+                }
+            }
+        }
+        if let Some(act) = act {
+            println!("{}", S::format_move(&act));
+            session.history.apply(act);
         }
-        println!("{:?}",act);
-        sol = sol.result(act.unwrap().clone());
     }
+    println!("deal #{} solved", opts.seed);
+    println!("{}", session.status());
+    session.finish(opts);
 }
@@ -1,10 +1,201 @@
-#[derive(Hash,PartialEq, Eq, Clone, Debug)]
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Suit {
+    Spades,
+    Hearts,
+    Clubs,
+    Diamonds
+}
+
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Color {
+    Black,
+    Red
+}
+
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Rank {
+    Ace,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    /// Only ever on a joker, and in no run with the other ranks.
+    Joker
+}
+
+/// Every suit, in the order the foundations have always been laid out.
+pub const SUITS: [Suit; 4] = [Suit::Spades, Suit::Hearts, Suit::Clubs, Suit::Diamonds];
+/// Every rank but the joker's, ace low.
+pub const RANKS: [Rank; 13] = [Rank::Ace, Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six, Rank::Seven,
+    Rank::Eight, Rank::Nine, Rank::Ten, Rank::Jack, Rank::Queen, Rank::King];
+
+const RANK_LETTERS: &str = "A23456789TJQK";
+const SUIT_LETTERS: &str = "SHCD";
+
+impl Suit {
+    /// The suit's place in `SUITS`.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+    pub fn color(&self) -> Color {
+        match *self {
+            Suit::Spades | Suit::Clubs => Color::Black,
+            Suit::Hearts | Suit::Diamonds => Color::Red
+        }
+    }
+    pub fn letter(&self) -> char {
+        SUIT_LETTERS.chars().nth(self.index()).unwrap()
+    }
+    pub fn symbol(&self) -> char {
+        match *self {
+            Suit::Spades => '♠',
+            Suit::Hearts => '♥',
+            Suit::Clubs => '♣',
+            Suit::Diamonds => '♦'
+        }
+    }
+    /// Reads a suit from its letter or symbol, hollow or filled.
+    pub fn from_char(c: char) -> Option<Suit> {
+        match c.to_ascii_uppercase() {
+            'S' | '♠' | '♤' => Some(Suit::Spades),
+            'H' | '♥' | '♡' => Some(Suit::Hearts),
+            'C' | '♣' | '♧' => Some(Suit::Clubs),
+            'D' | '♦' | '♢' => Some(Suit::Diamonds),
+            _ => None
+        }
+    }
+}
+
+impl Rank {
+    /// The rank's place in `RANKS`, from 0 for the ace to 12 for the king, or 13 for a joker.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+    /// What the card counts for, from 1 for the ace to 13 for the king.
+    pub fn value(&self) -> usize {
+        self.index() + 1
+    }
+    /// The rank one higher, if there is one.
+    pub fn next(&self) -> Option<Rank> {
+        RANKS.get(self.index() + 1).cloned()
+    }
+    /// The rank written as it is on the card: `A`, `2` to `10`, `J`, `Q`, `K`, or `X` for a
+    /// joker.
+    pub fn name(&self) -> String {
+        match *self {
+            Rank::Ten => String::from("10"),
+            _ => self.letter().to_string()
+        }
+    }
+    /// A single letter for the rank, with `T` for ten.
+    pub fn letter(&self) -> char {
+        match *self {
+            Rank::Joker => 'X',
+            _ => RANK_LETTERS.chars().nth(self.index()).unwrap()
+        }
+    }
+    /// Reads a rank written by `name` or `letter`.
+    pub fn from_name(name: &str) -> Option<Rank> {
+        if name == "10" {
+            return Some(Rank::Ten);
+        }
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => match c.to_ascii_uppercase() {
+                'X' => Some(Rank::Joker),
+                c => RANK_LETTERS.find(c).map(|i| RANKS[i])
+            },
+            _ => None
+        }
+    }
+}
+
+/// A playing card. With more than one deck in play the same card turns up more than once, and
+/// `deck` tells the copies apart.
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct Card {
-    pub suit: usize,
-    pub color: usize,
-    pub rank: usize
+    pub suit: Suit,
+    pub rank: Rank,
+    pub deck: usize
 }
 
+impl Card {
+    pub fn new(suit: Suit, rank: Rank) -> Card {
+        Card::from_deck(suit, rank, 0)
+    }
+    pub fn from_deck(suit: Suit, rank: Rank, deck: usize) -> Card {
+        Card {
+            suit,
+            rank,
+            deck
+        }
+    }
+    /// A joker. Decks come with a black one and a red one, which are given the suits of the
+    /// spades and the hearts.
+    pub fn joker(color: Color, deck: usize) -> Card {
+        let suit = match color {
+            Color::Black => Suit::Spades,
+            Color::Red => Suit::Hearts
+        };
+        Card::from_deck(suit, Rank::Joker, deck)
+    }
+    /// Whether this is a joker, which games that use them let stand in for any other card.
+    pub fn is_joker(&self) -> bool {
+        self.rank == Rank::Joker
+    }
+    pub fn color(&self) -> Color {
+        self.suit.color()
+    }
+    /// Identifies the card to the renderer, which needs every copy of a card kept apart.
+    pub fn ident(&self) -> Ident {
+        Ident::new((self.deck*4 + self.suit.index())*16 + self.rank.index())
+    }
+    /// A two letter code for the card, rank then suit, like `QH`, `TS` or `XS` for the black
+    /// joker. Copies from any deck but the first have the deck added, like `XS/1`.
+    pub fn code(&self) -> String {
+        match self.deck {
+            0 => format!("{}{}", self.rank.letter(), self.suit.letter()),
+            deck => format!("{}{}/{}", self.rank.letter(), self.suit.letter(), deck)
+        }
+    }
+    /// Reads back a card written by `code`, or written the way it is displayed, like `10♠`.
+    pub fn from_code(code: &str) -> Option<Card> {
+        let mut parts = code.splitn(2, '/');
+        let code = parts.next().unwrap();
+        let deck = match parts.next().map(|x| x.parse::<usize>()) {
+            Some(Ok(deck)) => deck,
+            Some(Err(_)) => return None,
+            None => 0
+        };
+        let suit = code.chars().last().and_then(Suit::from_char)?;
+        let rank = &code[..code.len() - code.chars().last().unwrap().len_utf8()];
+        Rank::from_name(rank).map(|rank| Card::from_deck(suit, rank, deck))
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.rank.name(), self.suit.symbol())
+    }
+}
+
+impl FromStr for Card {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Card, String> {
+        Card::from_code(text.trim()).ok_or(format!("'{}' is not a card", text))
+    }
+}
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub struct Ident(usize);
@@ -0,0 +1,125 @@
+use rand;
+use rand::{Rng, SeedableRng};
+use rand::isaac::Isaac64Rng;
+use game::cards::{Card, Color, Suit, Rank, SUITS, RANKS};
+
+/// What a game is dealt from: every rank in `ranks` of every suit in `suits`, as many times
+/// over as there are `decks`, along with some jokers.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Deck {
+    pub suits: Vec<Suit>,
+    pub ranks: Vec<Rank>,
+    pub decks: usize,
+    pub jokers: usize
+}
+
+impl Deck {
+    /// A standard 52 card deck.
+    pub fn standard() -> Deck {
+        Deck {
+            suits: SUITS.to_vec(),
+            ranks: RANKS.to_vec(),
+            decks: 1,
+            jokers: 0
+        }
+    }
+    /// Every card, a deck at a time in suit-major order, then the jokers, black and red in turn.
+    /// Each copy of a card, and each pair of jokers, is from its own deck.
+    pub fn cards(&self) -> Vec<Card> {
+        let mut out = Vec::new();
+        for deck in 0..self.decks {
+            for &suit in &self.suits {
+                for &rank in &self.ranks {
+                    out.push(Card::from_deck(suit, rank, deck));
+                }
+            }
+        }
+        for i in 0..self.jokers {
+            out.push(Card::joker(if i % 2 == 0 { Color::Black } else { Color::Red }, i / 2));
+        }
+        out
+    }
+    /// The cards shuffled by deal number.
+    pub fn shuffled(&self, seed: u64) -> Vec<Card> {
+        let mut cards = self.cards();
+        shuffle(&mut cards, seed);
+        cards
+    }
+}
+
+/// Picks a fresh deal number for when the player doesn't ask for one.
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// Shuffles `cards` in place. The same seed and the same starting order always give the same
+/// result, so a deal can be replayed from its number alone.
+pub fn shuffle(cards: &mut [Card], seed: u64) {
+    let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+    let len = cards.len();
+    for i in 0..len.saturating_sub(1) {
+        cards.swap(i, rng.gen_range(i as u64, len as u64) as usize);
+    }
+}
+
+/// The standard deck shuffled by deal number.
+pub fn shuffled(seed: u64) -> Vec<Card> {
+    Deck::standard().shuffled(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    #[test]
+    fn standard() {
+        let cards = Deck::standard().cards();
+        assert_eq!(cards.len(), 52);
+        assert_eq!(cards.iter().collect::<HashSet<_>>().len(), 52);
+        assert!(cards.iter().all(|x| x.deck == 0 && !x.is_joker()));
+    }
+
+    #[test]
+    fn stripped() {
+        let deck = Deck {
+            suits: vec![Suit::Spades, Suit::Hearts],
+            ranks: vec![Rank::Ace, Rank::Seven, Rank::King],
+            ..Deck::standard()
+        };
+        let cards = deck.cards();
+        assert_eq!(cards.len(), 6);
+        assert!(cards.iter().all(|x| deck.suits.contains(&x.suit) && deck.ranks.contains(&x.rank)));
+    }
+
+    #[test]
+    fn decks_and_jokers() {
+        let deck = Deck {
+            decks: 2,
+            jokers: 3,
+            ..Deck::standard()
+        };
+        let cards = deck.cards();
+        assert_eq!(cards.len(), 107);
+        // every copy of a card is told apart by its deck
+        assert_eq!(cards.iter().collect::<HashSet<_>>().len(), 107);
+        assert_eq!(cards.iter().filter(|x| x.deck == 1 && !x.is_joker()).count(), 52);
+        let jokers: Vec<&Card> = cards.iter().filter(|x| x.is_joker()).collect();
+        assert_eq!(jokers, vec![&Card::joker(Color::Black, 0), &Card::joker(Color::Red, 0), &Card::joker(Color::Black, 1)]);
+    }
+
+    #[test]
+    fn shuffled_by_number() {
+        let deck = Deck {
+            jokers: 2,
+            ..Deck::standard()
+        };
+        assert_eq!(deck.shuffled(7), deck.shuffled(7));
+        assert!(deck.shuffled(7) != deck.shuffled(8));
+        let mut cards = deck.shuffled(7);
+        cards.sort();
+        let mut unshuffled = deck.cards();
+        unshuffled.sort();
+        assert_eq!(cards, unshuffled);
+    }
+}
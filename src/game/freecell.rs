@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use game::cards::*;
+use game::problem::Problem;
+use game::render::*;
+use game::grid::*;
+use game::solitaire::{StackId, CardGameAction};
+use game::solver::Search;
+
+/// A game of FreeCell. Free cells are `StackId(0,_)`, cascades `StackId(1,_)` and foundations
+/// `StackId(2,_)`, with each foundation taking the suit matching its index.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct FreeCell {
+    cells: Vec<Option<Card>>,
+    cascades: Vec<Vec<Card>>,
+    goal: Vec<Vec<Card>>
+}
+
+/// Microsoft FreeCell numbers its suits clubs, diamonds, hearts, spades.
+const MS_SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+
+impl FreeCell {
+    /// Deals `cards` out across the cascades one at a time, left to right.
+    pub fn deal(cards: Vec<Card>, cells: usize, cascades: usize, suits: usize) -> Self {
+        let mut out = FreeCell {
+            cells: vec![None; cells],
+            cascades: vec![Vec::new(); cascades],
+            goal: vec![Vec::new(); suits]
+        };
+        for (i, card) in cards.into_iter().enumerate() {
+            out.cascades[i % cascades].push(card);
+        }
+        out
+    }
+    /// Deals the same game Microsoft FreeCell does for a given game number, from 1 to 32000.
+    pub fn deal_number(seed: u64) -> Self {
+        let mut seed = seed as u32;
+        let mut deck: Vec<usize> = (0..52).rev().collect();
+        for i in 0..51 {
+            seed = seed.wrapping_mul(214013).wrapping_add(2531011) & 0x7fffffff;
+            let j = 51 - (seed >> 16) as usize % (52 - i);
+            deck.swap(i, j);
+        }
+        let cards = deck.into_iter().map(|x| {
+            Card::new(MS_SUITS[x%4], RANKS[x/4])
+        }).collect();
+        FreeCell::deal(cards, 4, 8, 4)
+    }
+    /// How many cards can be moved at once as a sequence, using the free cells and empty
+    /// cascades as temporary space. Moving into an empty cascade leaves one fewer to use.
+    pub fn capacity(&self, to_empty: bool) -> usize {
+        let cells = self.cells.iter().filter(|x| x.is_none()).count();
+        let mut empty = self.cascades.iter().filter(|x| x.is_empty()).count();
+        if to_empty && empty > 0 {
+            empty -= 1;
+        }
+        (cells + 1) << empty
+    }
+    /// The index where the ordered run at the top of cascade `i` starts.
+    fn run_start(&self, i: usize) -> usize {
+        let stack = &self.cascades[i];
+        let mut start = stack.len().saturating_sub(1);
+        while start > 0 && builds_on(&stack[start], &stack[start-1]) {
+            start -= 1;
+        }
+        start
+    }
+    /// Finds the cell, cascade or foundation holding `card` and its index within it.
+    fn locate(&self, card: &Card) -> Option<(StackId, usize)> {
+        if let Some(i) = self.cells.iter().position(|x| x.as_ref() == Some(card)) {
+            return Some((StackId(0, i), 0));
+        }
+        for (i, stack) in self.cascades.iter().enumerate() {
+            if let Some(idx) = stack.iter().position(|x| x == card) {
+                return Some((StackId(1, i), idx));
+            }
+        }
+        for (i, stack) in self.goal.iter().enumerate() {
+            if let Some(idx) = stack.iter().position(|x| x == card) {
+                return Some((StackId(2, i), idx));
+            }
+        }
+        None
+    }
+    /// The number of cards in a cell, cascade or foundation.
+    fn len(&self, id: &StackId) -> Option<usize> {
+        match *id {
+            StackId(0, i) => self.cells.get(i).map(|x| x.iter().count()),
+            StackId(1, i) => self.cascades.get(i).map(|x| x.len()),
+            StackId(2, i) => self.goal.get(i).map(|x| x.len()),
+            _ => None
+        }
+    }
+    fn pop(&mut self, id: &StackId) -> Option<Card> {
+        match *id {
+            StackId(0, i) => self.cells[i].take(),
+            StackId(1, i) => self.cascades[i].pop(),
+            StackId(_, i) => self.goal[i].pop()
+        }
+    }
+    fn push(&mut self, id: &StackId, card: Card) {
+        match *id {
+            StackId(0, i) => self.cells[i] = Some(card),
+            StackId(1, i) => self.cascades[i].push(card),
+            StackId(_, i) => self.goal[i].push(card)
+        }
+    }
+    /// Moves the cards from `idx` up in `from` onto `to`, keeping their order.
+    fn shift(&mut self, from: &StackId, idx: usize, to: &StackId) {
+        let count = self.len(from).unwrap() - idx;
+        for _ in 0..count {
+            let card = self.pop(from).unwrap();
+            self.push(to, card);
+        }
+        let stack = match *to {
+            StackId(1, i) => &mut self.cascades[i],
+            StackId(2, i) => &mut self.goal[i],
+            _ => return
+        };
+        let len = stack.len();
+        stack[len - count..].reverse();
+    }
+}
+
+/// What it takes to put a `FreeCell` back the way it was before an action.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FreeCellUndo {
+    /// Cards were moved from `.0` onto `.1`, on top of the `.2` cards already there.
+    Move(StackId, StackId, usize),
+    /// The action did nothing.
+    Nothing
+}
+
+fn builds_on(card: &Card, under: &Card) -> bool {
+    card.color() != under.color() && card.rank.next() == Some(under.rank)
+}
+
+impl Problem<CardGameAction, FreeCellPercept> for FreeCell {
+    type Undo = FreeCellUndo;
+    fn percept(&self) -> FreeCellPercept {
+        let mut map = HashMap::new();
+        for (i, cell) in self.cells.iter().enumerate() {
+            map.insert(StackId(0,i), cell.iter().map(|x| (x.ident(), Some(x.clone()))).collect());
+        }
+        for (i, stack) in self.cascades.iter().enumerate() {
+            map.insert(StackId(1,i), stack.iter().map(|x| (x.ident(), Some(x.clone()))).collect());
+        }
+        for (i, stack) in self.goal.iter().enumerate() {
+            map.insert(StackId(2,i), stack.iter().map(|x| (x.ident(), Some(x.clone()))).collect());
+        }
+        FreeCellPercept {
+            stacks: map
+        }
+    }
+    fn actions(&self) -> Vec<CardGameAction> {
+        let mut out = Vec::new();
+        let mut singles: Vec<&Card> = self.cells.iter().filter_map(|x| x.as_ref()).collect();
+        for stack in &self.cascades {
+            if let Some(card) = stack.last() {
+                singles.push(card);
+            }
+        }
+        for card in &singles {
+            if self.goal[card.suit.index()].len() == card.rank.index() {
+                out.push(CardGameAction::Move((*card).clone(), StackId(2, card.suit.index())));
+            }
+        }
+        for i in 0..self.cascades.len() {
+            let stack = &self.cascades[i];
+            for start in self.run_start(i)..stack.len() {
+                let card = &stack[start];
+                let count = stack.len() - start;
+                for k in 0..self.cascades.len() {
+                    if k == i {
+                        continue;
+                    }
+                    let fits = match self.cascades[k].last() {
+                        Some(under) => builds_on(card, under) && count <= self.capacity(false),
+                        None => count <= self.capacity(true)
+                    };
+                    if fits {
+                        out.push(CardGameAction::Move(card.clone(), StackId(1,k)));
+                    }
+                }
+            }
+        }
+        for cell in self.cells.iter().filter_map(|x| x.as_ref()) {
+            for k in 0..self.cascades.len() {
+                if self.cascades[k].last().is_none_or(|under| builds_on(cell, under)) {
+                    out.push(CardGameAction::Move(cell.clone(), StackId(1,k)));
+                }
+            }
+        }
+        for free in (0..self.cells.len()).filter(|&i| self.cells[i].is_none()) {
+            for stack in &self.cascades {
+                if let Some(card) = stack.last() {
+                    out.push(CardGameAction::Move(card.clone(), StackId(0, free)));
+                }
+            }
+        }
+        out
+    }
+    fn result(&self, act: CardGameAction) -> Self {
+        let mut out = self.clone();
+        out.apply(act);
+        out
+    }
+    fn apply(&mut self, act: CardGameAction) -> FreeCellUndo {
+        let (card, to) = match act {
+            CardGameAction::Move(card, to) => (card, to),
+            _ => return FreeCellUndo::Nothing
+        };
+        let (from, idx) = match self.locate(&card) {
+            Some(x) => x,
+            None => return FreeCellUndo::Nothing
+        };
+        let below = match self.len(&to) {
+            Some(below) => below,
+            None => return FreeCellUndo::Nothing
+        };
+        // a cell holds a single card
+        if from == to || (to.0 == 0 && (below > 0 || idx + 1 < self.len(&from).unwrap())) {
+            return FreeCellUndo::Nothing;
+        }
+        self.shift(&from, idx, &to);
+        FreeCellUndo::Move(from, to, below)
+    }
+    fn undo(&mut self, undo: FreeCellUndo) {
+        if let FreeCellUndo::Move(from, to, below) = undo {
+            self.shift(&to, below, &from);
+        }
+    }
+    fn is_goal(&self) -> bool {
+        self.cells.iter().all(|x| x.is_none()) && self.cascades.iter().all(|x| x.is_empty())
+    }
+}
+
+impl FreeCell {
+    /// How much good it does to free `card`: 30 if it can go to the foundations, 10 if it can
+    /// go onto another cascade and 0 otherwise.
+    fn uncovers(&self, card: &Card) -> i32 {
+        if self.goal[card.suit.index()].len() == card.rank.index() {
+            30
+        } else if self.cascades.iter().any(|x| x.last().is_some_and(|top| builds_on(card, top))) {
+            10
+        } else {
+            0
+        }
+    }
+}
+
+impl Search<CardGameAction> for FreeCell {
+    type Key = FreeCell;
+    fn key(&self) -> FreeCell {
+        // which cell or cascade holds a card makes no difference
+        let mut key = self.clone();
+        key.cells.sort();
+        key.cascades.sort();
+        key
+    }
+    fn priority(&self, act: &CardGameAction) -> i32 {
+        let (card, target) = match act {
+            CardGameAction::Move(card, target) => (card, target),
+            _ => return 0
+        };
+        let from = self.cascades.iter().enumerate()
+            .filter_map(|(i, stack)| stack.iter().position(|x| x == card).map(|idx| (i, idx)))
+            .next();
+        match (target, from) {
+            (&StackId(2, _), _) => 100 - card.rank.index() as i32,
+            // an empty cascade is best spent on a king, which can't be moved anywhere better
+            (&StackId(1, k), None) => if !self.cascades[k].is_empty() { 60 } else if card.rank == Rank::King { 20 } else { -30 },
+            (&StackId(1, k), Some((i, idx))) => {
+                if idx > self.run_start(i) && self.cascades[k].is_empty() && self.cascades[i].len() - idx < self.capacity(true) {
+                    // only the longest part of a run that fits is worth moving to an empty cascade
+                    -70
+                } else if idx > self.run_start(i) {
+                    // splitting a run only shuffles it between cascades
+                    -50
+                } else if self.cascades[k].is_empty() {
+                    if idx == 0 { -100 } else if card.rank == Rank::King { 0 } else { -30 }
+                } else if idx == 0 {
+                    45
+                } else {
+                    25 + self.uncovers(&self.cascades[i][idx-1])
+                }
+            },
+            (&StackId(0, _), Some((i, idx))) if idx > self.run_start(i) => -60,
+            // trading an empty cascade for a full cell gains nothing
+            (&StackId(0, _), Some((_, 0))) => -90,
+            (&StackId(0, _), Some((i, idx))) => {
+                // filling a cell is only worth it when the card underneath has somewhere to go
+                let full = self.cells.iter().filter(|x| x.is_some()).count() as i32;
+                match self.uncovers(&self.cascades[i][idx-1]) {
+                    0 => -10 - 10*full,
+                    x => x
+                }
+            },
+            _ => -10
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct FreeCellPercept {
+    pub stacks: HashMap<StackId,Vec<(Ident,Option<Card>)>>
+}
+
+fn get_stack_location(&StackId(i, j): &StackId) -> GridLocation {
+    match i {
+        0 => GridLocation::new(GridValue(j as i32*2, 0), GridValue(0, 0), 0),
+        1 => GridLocation::new(GridValue(j as i32*2, 0), GridValue(2, 0), 0),
+        _ => GridLocation::new(GridValue(j as i32*2+8, 0), GridValue(0, 0), 0),
+    }
+}
+
+impl Renderable for FreeCellPercept {
+    type CardId = (StackId, Option<usize>);
+    type Action = CardGameAction;
+    fn get_cards(&self) -> Vec<Self::CardId> {
+        let mut out = Vec::new();
+        for (k, val) in self.stacks.iter() {
+            out.push((k.clone(), None));
+            for i in 0..val.len() {
+                out.push((k.clone(), Some(i)));
+            }
+        }
+        out
+    }
+    fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>> {
+        let stack = self.stacks.get(&id.0)?;
+        let idx = match id.1 {
+            Some(idx) => idx,
+            None => return Some(CardData {
+                ident: Ident::new((id.0).0*16 + (id.0).1).interleave(),
+                pos: get_stack_location(&id.0) - GridLocation::new(GridValue(0,0), GridValue(0,0), 1),
+                display: CardDisplay::Empty,
+                drag_children: None
+            })
+        };
+        let (ident, val) = stack.get(idx)?;
+        let mut offset = GridLocation::new(GridValue(0,0), GridValue(0,0), idx as i32);
+        let mut draggable = idx + 1 == stack.len() && (id.0).0 != 2;
+        let mut children = Vec::new();
+        if (id.0).0 == 1 {
+            offset.y = GridValue(0, idx as i32*2);
+            let ordered = (idx+1..stack.len()).all(|i| match (&stack[i].1, &stack[i-1].1) {
+                (Some(card), Some(under)) => builds_on(card, under),
+                _ => false
+            });
+            if ordered {
+                draggable = true;
+                for i in (idx+1)..stack.len() {
+                    children.push((id.0.clone(), Some(i)));
+                }
+            }
+        }
+        Some(CardData {
+            ident: ident.expand(),
+            pos: get_stack_location(&id.0) + offset,
+            display: match val {
+                Some(crd) => CardDisplay::Front(crd.clone()),
+                &None => CardDisplay::Back
+            },
+            drag_children: if draggable { Some(children) } else { None }
+        })
+    }
+    fn get_action_for(&self, act: MouseAction<Self::CardId>) -> Option<Self::Action> {
+        match act {
+            MouseAction::Drop(dragged, (stack, _)) => {
+                match self.get_data_for(dragged).map(|x| x.display) {
+                    Some(CardDisplay::Front(crd)) => Some(CardGameAction::Move(crd, stack)),
+                    _ => None
+                }
+            },
+            MouseAction::Tap(_) => None
+        }
+    }
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>> {
+        let top = |stack: &StackId| {
+            self.stacks.get(stack).map(|cards| (stack.clone(), cards.len().checked_sub(1)))
+        };
+        match act {
+            CardGameAction::Move(card, stack) => {
+                for (id, cards) in self.stacks.iter() {
+                    if let Some(idx) = cards.iter().position(|(_, x)| x.as_ref() == Some(card)) {
+                        return top(stack).map(|target| MouseAction::Drop((id.clone(), Some(idx)), target));
+                    }
+                }
+                None
+            },
+            CardGameAction::Tap(stack) => top(stack).map(MouseAction::Tap)
+        }
+    }
+    fn get_grid_extents() -> (GridLocation, GridLocation) {
+        (GridLocation::new(GridValue(-1,0), GridValue(-1,0), -1), GridLocation::new(GridValue(17,0), GridValue(3, 40), 20))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::problem::tests::walk;
+    use super::*;
+
+    fn cards(codes: &str) -> Vec<Card> {
+        codes.split_whitespace().map(|x| x.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn microsoft_deals() {
+        let game = FreeCell::deal_number(1);
+        let first: Vec<Card> = game.cascades.iter().map(|x| x[0].clone()).collect();
+        assert_eq!(first, cards("JD 2D 9H JC 5D 7H 7C 5H"));
+        assert_eq!(game.cascades[0], cards("JD KD 2S 4C 3S 6D 6S"));
+        assert_eq!(game.cascades[7], cards("5H 3H 3C 7S 7D TC"));
+    }
+
+    #[test]
+    fn empty_cascade() {
+        let game = FreeCell {
+            cells: vec![None; 4],
+            cascades: vec![cards("KC 9H 8S 7D"), cards("5D"), Vec::new()],
+            goal: vec![Vec::new(); 4]
+        };
+        let actions = game.actions();
+        for code in &["9H", "8S", "7D", "5D"] {
+            let act = CardGameAction::Move(code.parse().unwrap(), StackId(1, 2));
+            assert!(actions.contains(&act), "can't move {} to the empty cascade", code);
+        }
+        // with every cell full, only one card fits
+        let full = FreeCell {
+            cells: cards("AC AD AH AS").into_iter().map(Some).collect(),
+            ..game
+        };
+        let actions = full.actions();
+        assert!(actions.contains(&CardGameAction::Move("7D".parse().unwrap(), StackId(1, 2))));
+        assert!(!actions.contains(&CardGameAction::Move("8S".parse().unwrap(), StackId(1, 2))));
+    }
+
+    #[test]
+    fn undo() {
+        for seed in 1..40 {
+            walk(FreeCell::deal_number(seed), seed, 300, |_| ());
+        }
+    }
+}
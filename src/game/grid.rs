@@ -48,9 +48,9 @@ pub struct GridLocation {
 impl GridLocation {
     pub fn new(x:GridValue, y:GridValue, sort: i32) -> Self {
         GridLocation {
-            x: x,
-            y: y,
-            sort: sort
+            x,
+            y,
+            sort
         }
     }
 }
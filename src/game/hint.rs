@@ -0,0 +1,17 @@
+use game::problem::Problem;
+use game::solver::{Search, Outcome, ordered_actions, solve};
+
+/// Suggested actions for `state`, best first.
+///
+/// Actions are ranked by the search priority, with the first move of a solution found within
+/// `budget` positions moved to the front. Actions the priority marks as pointless are left out.
+pub fn hints<S, A, P>(state: &S, budget: usize) -> Vec<A> where S: Problem<A, P> + Search<A> + Clone, A: Clone + PartialEq {
+    let mut out: Vec<A> = ordered_actions(state).into_iter().rev().filter(|act| state.priority(act) >= 0).collect();
+    if let Outcome::Solved(acts) = solve(state, budget).outcome {
+        if let Some(first) = acts.into_iter().next() {
+            out.retain(|act| *act != first);
+            out.insert(0, first);
+        }
+    }
+    out
+}
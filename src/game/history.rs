@@ -0,0 +1,110 @@
+use game::problem::Problem;
+
+/// Records every action applied to a game along with the state it produced, so that moves can
+/// be taken back and replayed.
+///
+/// Actions are grouped into steps; undo and redo always work on whole steps. Most steps hold a
+/// single action, but a sequence started with `begin` and closed with `commit` (such as the
+/// automatic play to the foundations) is undone in one go.
+#[derive(Clone)]
+pub struct History<S, A> {
+    start: S,
+    done: Vec<Vec<(A, S)>>,
+    undone: Vec<Vec<(A, S)>>,
+    open: Option<Vec<(A, S)>>
+}
+
+impl <S, A> History<S, A> {
+    pub fn new(start: S) -> Self {
+        History {
+            start,
+            done: Vec::new(),
+            undone: Vec::new(),
+            open: None
+        }
+    }
+    pub fn current(&self) -> &S {
+        if let Some((_, state)) = self.open.as_ref().and_then(|step| step.last()) {
+            return state;
+        }
+        match self.done.last().and_then(|step| step.last()) {
+            Some((_, state)) => state,
+            None => &self.start
+        }
+    }
+    pub fn start(&self) -> &S {
+        &self.start
+    }
+    /// Every action applied so far, oldest first.
+    pub fn actions(&self) -> Vec<&A> {
+        self.done.iter().chain(self.open.iter()).flat_map(|step| step.iter().map(|(act, _)| act)).collect()
+    }
+    /// The actions in each undoable step, oldest first.
+    pub fn steps(&self) -> Vec<Vec<&A>> {
+        self.done.iter().chain(self.open.iter()).map(|step| step.iter().map(|(act, _)| act).collect()).collect()
+    }
+    /// Every action applied so far along with the states before and after it, oldest first.
+    pub fn transitions(&self) -> Vec<(&S, &A, &S)> {
+        let mut out = Vec::new();
+        let mut prev = &self.start;
+        for (act, state) in self.done.iter().chain(self.open.iter()).flat_map(|step| step.iter()) {
+            out.push((prev, act, state));
+            prev = state;
+        }
+        out
+    }
+    /// The number of undoable steps.
+    pub fn len(&self) -> usize {
+        self.done.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.done.is_empty()
+    }
+    /// Applies `act` to the current state and records it. Anything that had been undone is
+    /// forgotten.
+    pub fn apply<P>(&mut self, act: A) where S: Problem<A, P>, A: Clone {
+        let next = self.current().result(act.clone());
+        self.undone.clear();
+        match self.open {
+            Some(ref mut step) => step.push((act, next)),
+            None => self.done.push(vec![(act, next)])
+        }
+    }
+    /// Starts grouping applied actions into a single step.
+    pub fn begin(&mut self) {
+        self.commit();
+        self.open = Some(Vec::new());
+    }
+    /// Closes the group started by `begin`. Empty groups are dropped.
+    pub fn commit(&mut self) {
+        if let Some(step) = self.open.take() {
+            if !step.is_empty() {
+                self.done.push(step);
+            }
+        }
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        if let Some(step) = self.done.pop() {
+            self.undone.push(step);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        if let Some(step) = self.undone.pop() {
+            self.done.push(step);
+            true
+        } else {
+            false
+        }
+    }
+}
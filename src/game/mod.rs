@@ -1,5 +1,18 @@
 pub mod cards;
+pub mod deal;
+pub mod freecell;
+pub mod packed;
 pub mod problem;
+pub mod pyramid;
 pub mod render;
+pub mod rules;
+pub mod save;
+pub mod score;
+pub mod sequence;
 pub mod solitaire;
+pub mod solver;
+pub mod spider;
 pub mod grid;
+pub mod history;
+pub mod notation;
+pub mod hint;
@@ -0,0 +1,238 @@
+//! A compact text notation for moves, and replay files built on it.
+//!
+//! A move to a row is written `7H->T3` (rows count from one, left to right), a move to a
+//! foundation `AS->F2`, a move to a free cell `QD->C1` and a tap on the deck `draw`. When
+//! reading, a bare `F` or `C` picks whichever foundation or cell will take the card, `10` may be
+//! used in place of `T`, suits may be given by their symbols (`10♠`), and `recycle` means the
+//! same as `draw`. Jokers are `XS` and `XH`, with `/1` and so on after the second pair and up.
+//!
+//! With two decks there are two of every card, so Spider moves say where the cards are instead:
+//! `T3.5->T7` moves the cards from the fifth one up in the third column onto the seventh, and
+//! `deal` deals a row from the stock. `T3->T7` moves as many cards as will go.
+//!
+//! In Pyramid a pair of cards cleared together is written `5H+8C`, a king cleared on its own
+//! just `KS`, and turning over the stock `draw`. Golf and TriPeaks moves are just as short: the
+//! card played onto the waste, or `draw`.
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use game::cards::Card;
+use game::problem::Problem;
+use game::rules::Rules;
+use game::solitaire::{Solitaire, CardGameAction, StackId};
+use game::spider::SpiderAction;
+use game::pyramid::PyramidAction;
+use game::sequence::SequenceAction;
+
+/// Bumped whenever the replay format changes in a way older versions can't read.
+pub const REPLAY_VERSION: u32 = 1;
+
+pub fn format_move(act: &CardGameAction) -> String {
+    match *act {
+        CardGameAction::Move(ref card, StackId(0, i)) => format!("{}->C{}", card.code(), i+1),
+        CardGameAction::Move(ref card, StackId(1, i)) => format!("{}->T{}", card.code(), i+1),
+        CardGameAction::Move(ref card, StackId(2, i)) => format!("{}->F{}", card.code(), i+1),
+        CardGameAction::Move(ref card, StackId(i, j)) => format!("{}->{}.{}", card.code(), i, j),
+        CardGameAction::Tap(StackId(0, 0)) => String::from("draw"),
+        CardGameAction::Tap(StackId(i, j)) => format!("tap{}.{}", i, j)
+    }
+}
+
+/// Reads a move in `state`. Only legal moves are accepted.
+pub fn parse_move<S: Problem<CardGameAction, P>, P>(text: &str, state: &S) -> Result<CardGameAction, String> {
+    let text = text.trim();
+    let legal = state.actions();
+    if text == "draw" || text == "recycle" {
+        return legal.into_iter().find(|act| *act == CardGameAction::Tap(StackId(0,0)))
+            .ok_or(format!("can't {} now", text));
+    }
+    let parts: Vec<&str> = text.splitn(2, "->").collect();
+    if parts.len() != 2 {
+        return Err(format!("'{}' is not a move", text));
+    }
+    let card = Card::from_code(parts[0]).ok_or(format!("'{}' is not a card", parts[0]))?;
+    let target = parts[1];
+    let kind = match target.chars().next() {
+        Some('C') => 0,
+        Some('T') => 1,
+        Some('F') => 2,
+        _ => return Err(format!("'{}' is not a row, foundation or cell", target))
+    };
+    let index = if target.len() > 1 {
+        match target[1..].parse::<usize>() {
+            Ok(x) if x > 0 => Some(x-1),
+            _ => return Err(format!("'{}' is not a row, foundation or cell", target))
+        }
+    } else if kind != 1 {
+        None
+    } else {
+        return Err(format!("'{}' needs a row number", target))
+    };
+    legal.into_iter().find(|act| match act {
+        &CardGameAction::Move(ref x, StackId(i, j)) => *x == card && i == kind && index.is_none_or(|k| k == j),
+        _ => false
+    }).ok_or(format!("{} is not a legal move", text))
+}
+
+pub fn format_column_move(act: &SpiderAction) -> String {
+    match *act {
+        SpiderAction::Move(i, idx, k) => format!("T{}.{}->T{}", i+1, idx+1, k+1),
+        SpiderAction::Deal => String::from("deal")
+    }
+}
+
+/// Reads a move written by `format_column_move` in `state`. Only legal moves are accepted.
+pub fn parse_column_move<S: Problem<SpiderAction, P>, P>(text: &str, state: &S) -> Result<SpiderAction, String> {
+    let text = text.trim();
+    let legal = state.actions();
+    if text == "deal" {
+        return legal.into_iter().find(|act| *act == SpiderAction::Deal).ok_or(String::from("can't deal now"));
+    }
+    let column = |x: &str| match x.chars().next() {
+        Some('T') => x[1..].parse::<usize>().ok().and_then(|x| x.checked_sub(1)),
+        _ => None
+    };
+    let parts: Vec<&str> = text.splitn(2, "->").collect();
+    if parts.len() != 2 {
+        return Err(format!("'{}' is not a move", text));
+    }
+    let target = column(parts[1]).ok_or(format!("'{}' is not a column", parts[1]))?;
+    let from: Vec<&str> = parts[0].splitn(2, '.').collect();
+    let source = column(from[0]).ok_or(format!("'{}' is not a column", from[0]))?;
+    let index = match from.get(1) {
+        Some(x) => match x.parse::<usize>() {
+            Ok(x) if x > 0 => Some(x-1),
+            _ => return Err(format!("'{}' is not a card in the column", x))
+        },
+        None => None
+    };
+    // actions list the longest run from each column first
+    legal.into_iter().find(|act| match act {
+        &SpiderAction::Move(i, idx, k) => i == source && k == target && index.is_none_or(|x| x == idx),
+        _ => false
+    }).ok_or(format!("{} is not a legal move", text))
+}
+
+pub fn format_pair_move(act: &PyramidAction) -> String {
+    match act {
+        PyramidAction::Pair(a, b) => format!("{}+{}", a.code(), b.code()),
+        PyramidAction::King(card) => card.code(),
+        &PyramidAction::Draw => String::from("draw")
+    }
+}
+
+/// Reads a move written by `format_pair_move` in `state`, with the pair in either order. Only
+/// legal moves are accepted.
+pub fn parse_pair_move<S: Problem<PyramidAction, P>, P>(text: &str, state: &S) -> Result<PyramidAction, String> {
+    let text = text.trim();
+    let card = |x: &str| Card::from_code(x).ok_or(format!("'{}' is not a card", x));
+    let act = if text == "draw" || text == "recycle" {
+        PyramidAction::Draw
+    } else {
+        let parts: Vec<&str> = text.splitn(2, '+').collect();
+        if parts.len() == 2 {
+            PyramidAction::pair(card(parts[0])?, card(parts[1])?)
+        } else {
+            PyramidAction::King(card(text)?)
+        }
+    };
+    if state.actions().contains(&act) {
+        Ok(act)
+    } else {
+        Err(format!("{} is not a legal move", text))
+    }
+}
+
+pub fn format_play_move(act: &SequenceAction) -> String {
+    match act {
+        SequenceAction::Play(card) => card.code(),
+        &SequenceAction::Draw => String::from("draw")
+    }
+}
+
+/// Reads a move written by `format_play_move` in `state`. Only legal moves are accepted.
+pub fn parse_play_move<S: Problem<SequenceAction, P>, P>(text: &str, state: &S) -> Result<SequenceAction, String> {
+    let text = text.trim();
+    let act = if text == "draw" {
+        SequenceAction::Draw
+    } else {
+        SequenceAction::Play(Card::from_code(text).ok_or(format!("'{}' is not a card", text))?)
+    };
+    if state.actions().contains(&act) {
+        Ok(act)
+    } else {
+        Err(format!("{} is not a legal move", text))
+    }
+}
+
+/// A record of a whole game: the deal, the rules, and every move made.
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    pub actions: Vec<CardGameAction>
+}
+
+impl Replay {
+    pub fn start(&self) -> Solitaire {
+        Solitaire::deal_number(self.seed, self.rules.clone())
+    }
+    /// Plays every move from the deal, returning the final state.
+    pub fn play(&self) -> Solitaire {
+        let mut state = self.start();
+        for act in &self.actions {
+            state = state.result(act.clone());
+        }
+        state
+    }
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "solitaire replay {}", REPLAY_VERSION)?;
+        writeln!(file, "seed {}", self.seed)?;
+        writeln!(file, "rules {}", self.rules.to_text())?;
+        for line in self.actions.chunks(10) {
+            let moves: Vec<String> = line.iter().map(format_move).collect();
+            writeln!(file, "{}", moves.join(" "))?;
+        }
+        Ok(())
+    }
+    /// Loads a replay written by `write`. Anything after a `#` on a line is a comment. Every
+    /// move is checked against the game as it is played, so an illegal move is an error.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Replay, String> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path).and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Replay::parse(&text).map_err(|e| format!("{} is not a usable replay: {}", path.display(), e))
+    }
+    fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().map(|x| x.split('#').next().unwrap().trim()).filter(|x| !x.is_empty());
+        match lines.next() {
+            Some(x) if x == format!("solitaire replay {}", REPLAY_VERSION) => (),
+            Some(x) if x.starts_with("solitaire replay ") => {
+                return Err(format!("it was written by an incompatible version (format {}, expected {})", &x[17..], REPLAY_VERSION));
+            },
+            _ => return Err(String::from("it does not start with a replay header"))
+        }
+        let seed = match lines.next() {
+            Some(x) if x.starts_with("seed ") => x[5..].trim().parse().map_err(|_| String::from("bad seed"))?,
+            _ => return Err(String::from("missing seed"))
+        };
+        let rules = match lines.next() {
+            Some(x) if x.starts_with("rules ") => Rules::from_text(&x[6..])?,
+            _ => return Err(String::from("missing rules"))
+        };
+        let mut out = Replay {
+            seed,
+            rules,
+            actions: Vec::new()
+        };
+        let mut state = out.start();
+        for word in lines.flat_map(|x| x.split_whitespace()) {
+            let act = parse_move(word, &state).map_err(|e| format!("move {}: {}", out.actions.len()+1, e))?;
+            state = state.result(act.clone());
+            out.actions.push(act);
+        }
+        Ok(out)
+    }
+}
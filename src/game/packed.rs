@@ -0,0 +1,527 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use game::cards::*;
+use game::problem::Problem;
+use game::rules::{Rules, Building, Moving, Empty, Stock};
+use game::solitaire::{Solitaire, CardGameAction, CardGamePercept, StackId};
+use game::solver::Search;
+
+/// The most cards a packed game can hold: a deck and up to eight jokers.
+const CAPACITY: usize = 64;
+const ROWS: usize = 7;
+const GOALS: usize = 4;
+/// Where each kind of stack starts among the packed stacks.
+const DECK: usize = 0;
+const RUNOFF: usize = 1;
+const ROW: usize = 2;
+const GOAL: usize = ROW + ROWS;
+const STACKS: usize = GOAL + GOALS;
+/// The hash slots after the ones for cards in stacks, one for each card that can be at the
+/// bottom of a pile of face down cards.
+const HIDDEN: usize = STACKS * CAPACITY;
+const REDEALS: usize = HIDDEN + 256;
+
+/// A card as a byte: the same number its `Ident` is made from, which fits as long as the card
+/// comes from one of the first four decks.
+fn pack_card(card: &Card) -> u8 {
+    ((card.deck*4 + card.suit.index())*16 + card.rank.index()) as u8
+}
+
+fn unpack_card(x: u8) -> Card {
+    let x = x as usize;
+    let rank = match x % 16 {
+        13 => Rank::Joker,
+        i => RANKS[i]
+    };
+    Card::from_deck(SUITS[x/16 % 4], rank, x/64)
+}
+
+/// The number standing for `value` in hash slot `slot`. They are spread out by SplitMix64, so
+/// they don't need to be kept in a table, and are the same on every run.
+fn zobrist(slot: usize, value: usize) -> u64 {
+    let mut z = ((slot*256 + value) as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The hash of card `x` at `pos` in stack `s`.
+fn card_hash(s: usize, pos: usize, x: u8) -> u64 {
+    zobrist(s * CAPACITY + pos, x as usize)
+}
+
+/// The same game as a `Solitaire`, packed into one fixed size block with no allocations, so
+/// copying it for every position searched is cheap. Every stack is kept end to end in `cards`,
+/// and the position is hashed as it changes.
+///
+/// It plays exactly like the `Solitaire` it was packed from, offering the same actions in the
+/// same order, so the solver finds the same solutions in either.
+pub struct PackedSolitaire {
+    cards: [u8; CAPACITY],
+    /// Where each stack ends in `cards`: the deck, the runoff, the rows, then the foundations.
+    ends: [u8; STACKS],
+    hidden: [u8; ROWS],
+    rows: u8,
+    goals: u8,
+    base: u8,
+    redeals: usize,
+    hash: u64,
+    rules: Rules
+}
+
+impl Clone for PackedSolitaire {
+    fn clone(&self) -> Self {
+        PackedSolitaire {
+            cards: self.cards,
+            ends: self.ends,
+            hidden: self.hidden,
+            rows: self.rows,
+            goals: self.goals,
+            base: self.base,
+            redeals: self.redeals,
+            hash: self.hash,
+            rules: self.rules.clone()
+        }
+    }
+}
+
+impl PartialEq for PackedSolitaire {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.cards[..] == other.cards[..] && self.ends == other.ends &&
+            self.hidden == other.hidden && self.redeals == other.redeals && self.base == other.base &&
+            self.rows == other.rows && self.goals == other.goals && self.rules == other.rules
+    }
+}
+
+impl Eq for PackedSolitaire {}
+
+impl fmt::Debug for PackedSolitaire {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.unpack())
+    }
+}
+
+impl Hash for PackedSolitaire {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl PackedSolitaire {
+    /// Packs a game, as long as it has no more than seven rows, four foundations and 64 cards
+    /// from the first four decks.
+    pub fn pack(game: &Solitaire) -> Result<PackedSolitaire, String> {
+        let (rows, goals) = game.size();
+        if rows > ROWS || goals > GOALS {
+            return Err(format!("only {} rows and {} foundations can be packed", ROWS, GOALS));
+        }
+        let mut out = PackedSolitaire {
+            cards: [0; CAPACITY],
+            ends: [0; STACKS],
+            hidden: [0; ROWS],
+            rows: rows as u8,
+            goals: goals as u8,
+            base: game.base().index() as u8,
+            redeals: game.redeals(),
+            hash: 0,
+            rules: game.rules().clone()
+        };
+        let mut len = 0;
+        for s in 0..STACKS {
+            let id = match s {
+                DECK => StackId(0,0),
+                RUNOFF => StackId(0,1),
+                s if s < GOAL => StackId(1, s - ROW),
+                s => StackId(2, s - GOAL)
+            };
+            for card in game.stack(&id).map_or(&[][..], |x| &x[..]) {
+                if len == CAPACITY || card.deck >= 4 {
+                    return Err(String::from("too many cards to pack"));
+                }
+                out.cards[len] = pack_card(card);
+                len += 1;
+            }
+            out.ends[s] = len as u8;
+        }
+        for i in 0..rows {
+            out.hidden[i] = game.hidden(i) as u8;
+        }
+        out.hash = out.full_hash();
+        Ok(out)
+    }
+    /// Turns the game back into a `Solitaire`.
+    pub fn unpack(&self) -> Solitaire {
+        let cards = |s: usize| self.stack(s).iter().cloned().map(unpack_card).collect::<Vec<_>>();
+        let rows = (0..self.rows as usize).map(|i| (self.hidden[i] as usize, cards(ROW + i))).collect();
+        let goals = (0..self.goals as usize).map(|k| cards(GOAL + k)).collect();
+        Solitaire::from_stacks(cards(DECK), cards(RUNOFF), rows, goals, RANKS[self.base as usize], self.redeals, self.rules.clone())
+    }
+    /// Hashes the whole position from scratch. Moves keep `hash` up to date without this.
+    fn full_hash(&self) -> u64 {
+        let mut out = self.redeal_hash();
+        for s in 0..STACKS {
+            for (pos, &x) in self.stack(s).iter().enumerate() {
+                out ^= card_hash(s, pos, x);
+            }
+        }
+        for i in 0..self.rows as usize {
+            out ^= self.hidden_hash(i, self.hidden[i] as usize);
+        }
+        out
+    }
+    /// The hash of `hidden` face down cards in row `i`. It goes by the bottom card rather than the
+    /// row, which stays put for as long as any of them are left.
+    fn hidden_hash(&self, i: usize, hidden: usize) -> u64 {
+        match hidden {
+            0 => 0,
+            _ => zobrist(HIDDEN + self.stack(ROW + i)[0] as usize, hidden)
+        }
+    }
+    /// Without a limit on passes, the number taken so far makes no difference, so it is left out
+    /// of the hash.
+    fn redeal_hash(&self) -> u64 {
+        match self.rules.passes {
+            Some(_) => zobrist(REDEALS, self.redeals),
+            None => 0
+        }
+    }
+    fn start(&self, s: usize) -> usize {
+        if s == 0 { 0 } else { self.ends[s-1] as usize }
+    }
+    fn stack(&self, s: usize) -> &[u8] {
+        &self.cards[self.start(s)..self.ends[s] as usize]
+    }
+    fn top(&self, s: usize) -> Option<u8> {
+        self.stack(s).last().cloned()
+    }
+    /// The packed stack for a `StackId`, if there is one.
+    fn slot(&self, id: &StackId) -> Option<usize> {
+        match *id {
+            StackId(0,0) => Some(DECK),
+            StackId(0,1) => Some(RUNOFF),
+            StackId(1,x) if x < self.rows as usize => Some(ROW + x),
+            StackId(2,x) if x < self.goals as usize => Some(GOAL + x),
+            _ => None
+        }
+    }
+    /// Finds the stack holding card `x` and its index within that stack.
+    fn locate(&self, x: u8) -> Option<(usize, usize)> {
+        let len = self.ends[STACKS-1] as usize;
+        self.cards[..len].iter().position(|&y| y == x).map(|pos| {
+            let s = (0..STACKS).find(|&s| pos < self.ends[s] as usize).unwrap();
+            (s, pos - self.start(s))
+        })
+    }
+    /// Moves the cards from `idx` up in stack `from` onto stack `to`, keeping their order.
+    fn shift(&mut self, from: usize, idx: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        let begin = self.start(from) + idx;
+        let end = self.ends[from] as usize;
+        let count = end - begin;
+        let below = self.ends[to] as usize - self.start(to);
+        let mut run = [0; CAPACITY];
+        run[..count].copy_from_slice(&self.cards[begin..end]);
+        for (j, &x) in run[..count].iter().enumerate() {
+            self.hash ^= card_hash(from, idx + j, x) ^ card_hash(to, below + j, x);
+        }
+        if to > from {
+            // close the gap, then open one at the end of `to`
+            let target = self.ends[to] as usize;
+            for pos in end..target {
+                self.cards[pos - count] = self.cards[pos];
+            }
+            self.cards[target - count..target].copy_from_slice(&run[..count]);
+            for s in from..to {
+                self.ends[s] -= count as u8;
+            }
+        } else if to < from {
+            let target = self.ends[to] as usize;
+            for pos in (target..begin).rev() {
+                self.cards[pos + count] = self.cards[pos];
+            }
+            self.cards[target..target + count].copy_from_slice(&run[..count]);
+            for s in to..from {
+                self.ends[s] += count as u8;
+            }
+        }
+    }
+    /// Moves the top card of `from` onto `to`, if there is one.
+    fn shift_top(&mut self, from: usize, to: usize) {
+        let len = self.stack(from).len();
+        if len > 0 {
+            self.shift(from, len - 1, to);
+        }
+    }
+    fn set_hidden(&mut self, i: usize, hidden: usize) {
+        self.hash = self.hash ^ self.hidden_hash(i, self.hidden[i] as usize) ^ self.hidden_hash(i, hidden);
+        self.hidden[i] = hidden as u8;
+    }
+    fn can_redeal(&self) -> bool {
+        match self.rules.passes {
+            Some(passes) => self.redeals + 1 < passes,
+            None => true
+        }
+    }
+    /// The rank foundation `k` takes next, if it isn't full. Jokers put away on it don't count.
+    fn next_rank(&self, k: usize) -> Option<Rank> {
+        match self.stack(GOAL + k).iter().filter(|&&x| x % 16 != 13).count() {
+            13 => None,
+            len if self.base as usize + len >= 13 && !self.rules.wrap => None,
+            len => Some(RANKS[(self.base as usize + len) % 13])
+        }
+    }
+    /// Whether the card at `j` in row `i` can be picked up along with everything on top of it.
+    fn movable(&self, i: usize, j: usize) -> bool {
+        let stack = self.stack(ROW + i);
+        (j+1..stack.len()).all(|k| {
+            let (card, under) = (unpack_card(stack[k]), unpack_card(stack[k-1]));
+            match self.rules.moves {
+                Moving::Any => true,
+                Moving::Built => self.rules.builds(&card, &under),
+                Moving::InSuit => Building::SameSuit.allows(&card, &under) && self.rules.builds(&card, &under)
+            }
+        })
+    }
+}
+
+impl Problem<CardGameAction, CardGamePercept> for PackedSolitaire {
+    /// The position from before is small enough to keep whole.
+    type Undo = PackedSolitaire;
+    fn percept(&self) -> CardGamePercept {
+        self.unpack().percept()
+    }
+    fn actions(&self) -> Vec<CardGameAction> {
+        let mut out = Vec::new();
+        let mut considerations = Vec::new();
+        let rows = self.rows as usize;
+        let refills = match self.rules.stock {
+            Stock::Runoff => !self.stack(RUNOFF).is_empty() && self.can_redeal(),
+            Stock::Rows => false
+        };
+        if !self.stack(DECK).is_empty() || refills {
+            out.push(CardGameAction::Tap(StackId(0,0)));
+        }
+        considerations.extend(self.top(RUNOFF));
+        for i in 0..rows {
+            for j in self.hidden[i] as usize..self.stack(ROW + i).len() {
+                if self.movable(i, j) {
+                    considerations.push(self.stack(ROW + i)[j]);
+                }
+            }
+        }
+        for k in 0..self.goals as usize {
+            considerations.extend(self.top(GOAL + k));
+        }
+        let king = RANKS[(self.base as usize + 12) % 13];
+        for current in considerations.drain(..).map(unpack_card) {
+            for k in 0..rows {
+                let fits = match self.top(ROW + k) {
+                    Some(test) => self.rules.builds(&current, &unpack_card(test)) && !self.stack(ROW + k).contains(&pack_card(&current)),
+                    None => self.rules.empty == Empty::AnyCard || current.is_joker() || current.rank == king
+                };
+                if fits {
+                    out.push(CardGameAction::Move(current.clone(), StackId(1,k)));
+                }
+            }
+        }
+        considerations.extend(self.top(RUNOFF));
+        for i in 0..rows {
+            considerations.extend(self.top(ROW + i));
+        }
+        for current in considerations.drain(..).map(unpack_card) {
+            for k in 0..self.goals as usize {
+                let goal = self.stack(GOAL + k);
+                let fits = if current.is_joker() {
+                    !goal.is_empty()
+                } else {
+                    Some(current.rank) == self.next_rank(k) && goal.first().is_none_or(|&x| unpack_card(x).suit == current.suit)
+                };
+                if fits {
+                    out.push(CardGameAction::Move(current.clone(), StackId(2,k)));
+                }
+            }
+        }
+        out
+    }
+    fn result(&self, act: CardGameAction) -> Self {
+        let mut out = self.clone();
+        match act {
+            CardGameAction::Tap(StackId(0,0)) => {
+                if out.rules.stock == Stock::Rows {
+                    for i in 0..out.rows as usize {
+                        out.shift_top(DECK, ROW + i);
+                    }
+                } else if out.stack(DECK).is_empty() {
+                    if out.can_redeal() {
+                        while !out.stack(RUNOFF).is_empty() {
+                            out.shift_top(RUNOFF, DECK);
+                        }
+                        out.hash ^= out.redeal_hash();
+                        out.redeals += 1;
+                        out.hash ^= out.redeal_hash();
+                    }
+                } else {
+                    for _ in 0..out.rules.draw {
+                        out.shift_top(DECK, RUNOFF);
+                    }
+                }
+            },
+            CardGameAction::Move(card, stack) => {
+                if let (Some((from, idx)), Some(to)) = (out.locate(pack_card(&card)), out.slot(&stack)) {
+                    out.shift(from, idx, to);
+                }
+                for i in 0..out.rows as usize {
+                    let len = out.stack(ROW + i).len();
+                    if len <= out.hidden[i] as usize && len > 0 {
+                        let hidden = out.hidden[i] as usize - 1;
+                        out.set_hidden(i, hidden);
+                    }
+                }
+            },
+            _ => ()
+        }
+        out
+    }
+    fn apply(&mut self, act: CardGameAction) -> PackedSolitaire {
+        let next = self.result(act);
+        mem::replace(self, next)
+    }
+    fn undo(&mut self, undo: PackedSolitaire) {
+        *self = undo;
+    }
+    fn is_goal(&self) -> bool {
+        self.ends[GOAL-1] == 0
+    }
+}
+
+impl Search<CardGameAction> for PackedSolitaire {
+    type Key = PackedSolitaire;
+    /// Like `Solitaire::canonical`, the rows sorted unless the deck is still to be dealt onto
+    /// them, the foundations sorted by suit and, without a limit on passes, no count of redeals.
+    /// Unlike it, the face down cards are all kept rather than just the bottom one, so positions
+    /// that only differ in the order of those are told apart here.
+    fn key(&self) -> PackedSolitaire {
+        let mut key = self.clone();
+        if key.rules.passes.is_none() {
+            key.redeals = 0;
+        }
+        if self.rules.stock == Stock::Runoff || self.stack(DECK).is_empty() {
+            let mut order: Vec<usize> = (0..self.rows as usize).collect();
+            order.sort_by_key(|&i| (self.stack(ROW + i), self.hidden[i]));
+            let mut len = self.start(ROW);
+            for (i, &from) in order.iter().enumerate() {
+                let stack = self.stack(ROW + from);
+                key.cards[len..len + stack.len()].copy_from_slice(stack);
+                len += stack.len();
+                key.ends[ROW + i] = len as u8;
+                key.hidden[i] = self.hidden[from];
+            }
+        }
+        let mut order: Vec<usize> = (0..self.goals as usize).collect();
+        order.sort_by_key(|&k| self.stack(GOAL + k).first().map(|&x| x/16 % 4));
+        let mut len = self.start(GOAL);
+        for (k, &from) in order.iter().enumerate() {
+            let stack = self.stack(GOAL + from);
+            key.cards[len..len + stack.len()].copy_from_slice(stack);
+            len += stack.len();
+            key.ends[GOAL + k] = len as u8;
+        }
+        key.hash = key.full_hash();
+        key
+    }
+    fn priority(&self, act: &CardGameAction) -> i32 {
+        let empty = |k: usize| self.stack(GOAL + k).is_empty();
+        match *act {
+            // every empty foundation is as good as the first
+            CardGameAction::Move(_, StackId(2, k)) if empty(k) && (0..k).any(&empty) => -1,
+            // a joker can be put away whenever it's free, and one foundation is as good as another
+            CardGameAction::Move(ref card, StackId(2, k)) if card.is_joker() => {
+                if (0..k).any(|x| !empty(x)) { -1 } else { 5 }
+            },
+            CardGameAction::Move(ref card, StackId(2, _)) => 100 - ((card.rank.index() + 13 - self.base as usize) % 13) as i32,
+            CardGameAction::Move(ref card, StackId(1, k)) => {
+                match self.locate(pack_card(card)) {
+                    // moving a whole column into an empty one gains nothing
+                    Some((s, 0)) if (ROW..GOAL).contains(&s) && self.stack(ROW + k).is_empty() => -100,
+                    Some((s, idx)) if (ROW..GOAL).contains(&s) && idx == self.hidden[s - ROW] as usize => 50 + idx as i32,
+                    // splitting a built sequence is rarely useful, so try it after everything else
+                    Some((s, idx)) if (ROW..GOAL).contains(&s) && self.rules.builds(card, &unpack_card(self.stack(s)[idx-1])) => -50,
+                    Some((s, _)) if (ROW..GOAL).contains(&s) => 20,
+                    // only worth it to make room for something else, and never worth a hint
+                    Some((s, _)) if s >= GOAL => -40,
+                    _ => 30
+                }
+            },
+            _ => 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::isaac::Isaac64Rng;
+    use game::problem::tests::walk;
+    use super::*;
+
+    /// Plays the same random moves in a run of deals and their packed copies side by side,
+    /// checking at every step that the two still agree, and that the hash kept up move by move is
+    /// the one the position hashes to from scratch.
+    fn side_by_side(rules: Rules) {
+        for seed in 0..30 {
+            let mut game = Solitaire::deal_number(seed, rules.clone());
+            let mut packed = PackedSolitaire::pack(&game).unwrap();
+            let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+            for _ in 0..300 {
+                assert_eq!(packed.unpack(), game);
+                assert_eq!(PackedSolitaire::pack(&game).unwrap(), packed);
+                assert_eq!(packed.hash, packed.full_hash());
+                let key = packed.key();
+                assert_eq!(key.hash, key.full_hash());
+                let actions = game.actions();
+                assert_eq!(packed.actions(), actions);
+                if actions.is_empty() {
+                    break;
+                }
+                let act = actions[rng.gen_range(0, actions.len())].clone();
+                game.apply(act.clone());
+                packed.apply(act);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        side_by_side(Rules::default());
+        side_by_side(Rules { draw: 1, passes: Some(3), jokers: 2, ..Rules::default() });
+        for name in &["agnes", "easthaven", "yukon", "whitehead"] {
+            side_by_side(Rules::named(name).unwrap());
+        }
+    }
+
+    fn rows(rows: &[&str]) -> PackedSolitaire {
+        let rows = rows.iter().map(|x| (0, x.split_whitespace().map(|x| x.parse().unwrap()).collect())).collect();
+        PackedSolitaire::pack(&Solitaire::from_stacks(Vec::new(), Vec::new(), rows, vec![Vec::new(); 4], Rank::Ace, 0, Rules::default())).unwrap()
+    }
+
+    #[test]
+    fn hash_tells_rows_apart() {
+        let game = rows(&["5D 9C", "7H 3S"]);
+        assert!(game.hash != rows(&["5D 3S", "7H 9C"]).hash);
+        // while rows in another order are still the same position to the solver
+        let swapped = rows(&["7H 3S", "5D 9C"]);
+        assert!(game.hash != swapped.hash);
+        assert_eq!(game.key(), swapped.key());
+        assert_eq!(game.key().hash, swapped.key().hash);
+    }
+
+    #[test]
+    fn undo() {
+        for seed in 0..20 {
+            walk(PackedSolitaire::pack(&Solitaire::deal_number(seed, Rules::default())).unwrap(), seed, 300, |_| ());
+        }
+    }
+}
@@ -1,8 +1,45 @@
 pub trait Problem<A, P> {
+    /// What `apply` hands back so that `undo` can take the action back.
+    type Undo;
     fn percept(&self) -> P;
     fn actions(&self) -> Vec<A>;
-    fn result(&self, A) -> Self;
+    fn result(&self, _: A) -> Self;
+    /// Plays `act` in place, with the same effect as `result`.
+    fn apply(&mut self, act: A) -> Self::Undo;
+    /// Takes back an action played by `apply`. Actions have to be taken back most recent first.
+    fn undo(&mut self, undo: Self::Undo);
     fn is_goal(&self) -> bool;
 }
 
+#[cfg(test)]
+pub mod tests {
+    use std::fmt::Debug;
+    use rand::{Rng, SeedableRng};
+    use rand::isaac::Isaac64Rng;
+    use game::solver::Search;
+    use super::Problem;
 
+    /// Takes a random walk of up to `steps` actions from `start`, checking at every step that
+    /// `undo` puts back exactly what `apply` changed, down to the solver's key, and that `apply`
+    /// does the same as `result`. Every undo is shown to `seen` before it is used.
+    pub fn walk<S, A, P, F>(start: S, seed: u64, steps: usize, mut seen: F) where S: Problem<A, P> + Search<A> + Clone + PartialEq + Debug, A: Clone + Debug, F: FnMut(&S::Undo) {
+        let mut game = start;
+        let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+        for _ in 0..steps {
+            let actions = game.actions();
+            if actions.is_empty() {
+                break;
+            }
+            let act = actions[rng.gen_range(0, actions.len())].clone();
+            let before = game.clone();
+            let undo = game.apply(act.clone());
+            assert_eq!(game, before.result(act.clone()), "{:?}", act);
+            seen(&undo);
+            let after = game.clone();
+            game.undo(undo);
+            assert_eq!(game, before, "undoing {:?}", act);
+            assert!(game.key() == before.key());
+            game = after;
+        }
+    }
+}
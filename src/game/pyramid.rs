@@ -0,0 +1,404 @@
+use game::cards::*;
+use game::problem::Problem;
+use game::render::*;
+use game::grid::*;
+use game::solitaire::StackId;
+use game::solver::Search;
+use game::deal;
+
+/// A game of Pyramid. Cards are cleared in pairs adding up to thirteen, counting jacks as 11 and
+/// queens as 12, and kings are cleared on their own. Only cards with nothing overlapping them in
+/// the row below can be played, along with the top of the waste.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Pyramid {
+    rows: Vec<Vec<Option<Card>>>,
+    stock: Vec<Card>,
+    waste: Vec<Card>,
+    discard: Vec<Card>,
+    redeals: usize,
+    passes: Option<usize>
+}
+
+/// Pairs are written lowest rank first, so each pair has just one way of being written.
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub enum PyramidAction {
+    Pair(Card, Card),
+    King(Card),
+    /// Turns over the top of the stock, or the waste back over once the stock runs out.
+    Draw
+}
+
+impl PyramidAction {
+    pub fn pair(a: Card, b: Card) -> PyramidAction {
+        if a.rank < b.rank {
+            PyramidAction::Pair(a, b)
+        } else {
+            PyramidAction::Pair(b, a)
+        }
+    }
+}
+
+/// Whether the card at row `r`, position `c` has a card overlapping it.
+fn covered<T>(rows: &[Vec<Option<T>>], r: usize, c: usize) -> bool {
+    match rows.get(r+1) {
+        Some(next) => next[c].is_some() || next[c+1].is_some(),
+        None => false
+    }
+}
+
+impl Pyramid {
+    /// Lays out a pyramid `height` rows high from the top of `cards`, leaving the rest as the
+    /// stock. `passes` limits how many times the stock can be gone through.
+    pub fn deal(mut cards: Vec<Card>, height: usize, passes: Option<usize>) -> Self {
+        let mut rows = Vec::new();
+        for r in 0..height {
+            let mut row = Vec::new();
+            for _ in 0..r+1 {
+                row.push(cards.pop());
+            }
+            rows.push(row);
+        }
+        Pyramid {
+            rows,
+            stock: cards,
+            waste: Vec::new(),
+            discard: Vec::new(),
+            redeals: 0,
+            passes
+        }
+    }
+    pub fn deal_number(seed: u64, passes: Option<usize>) -> Self {
+        Pyramid::deal(deal::shuffled(seed), 7, passes)
+    }
+    pub fn can_redeal(&self) -> bool {
+        match self.passes {
+            Some(passes) => self.redeals + 1 < passes,
+            None => true
+        }
+    }
+    /// Every card that can be played right now.
+    fn free(&self) -> Vec<&Card> {
+        let mut out: Vec<&Card> = self.waste.last().into_iter().collect();
+        for (r, row) in self.rows.iter().enumerate() {
+            for (c, card) in row.iter().enumerate() {
+                if let Some(card) = card {
+                    if !covered(&self.rows, r, c) {
+                        out.push(card);
+                    }
+                }
+            }
+        }
+        out
+    }
+    /// Where `card` can be taken from, if it's on top of the waste or in the pyramid.
+    fn place(&self, card: &Card) -> Option<Place> {
+        if self.waste.last() == Some(card) {
+            return Some(Place::Waste);
+        }
+        for (r, row) in self.rows.iter().enumerate() {
+            if let Some(c) = row.iter().position(|x| x.as_ref() == Some(card)) {
+                return Some(Place::Slot(r, c));
+            }
+        }
+        None
+    }
+    /// Moves the card at `place` onto the discard pile.
+    fn take(&mut self, place: Place) {
+        let card = match place {
+            Place::Waste => self.waste.pop(),
+            Place::Slot(r, c) => self.rows[r][c].take()
+        };
+        self.discard.push(card.unwrap());
+    }
+    /// Puts the top of the discard pile back at `place`.
+    fn put_back(&mut self, place: Place) {
+        let card = self.discard.pop().unwrap();
+        match place {
+            Place::Waste => self.waste.push(card),
+            Place::Slot(r, c) => self.rows[r][c] = Some(card)
+        }
+    }
+    /// The row a card is in, if it's in the pyramid.
+    fn row_of(&self, card: &Card) -> Option<usize> {
+        self.rows.iter().position(|row| row.iter().any(|x| x.as_ref() == Some(card)))
+    }
+}
+
+/// Where a card was taken from: the top of the waste, or a row and position in the pyramid.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Place {
+    Waste,
+    Slot(usize, usize)
+}
+
+/// What it takes to put a `Pyramid` back the way it was before an action.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PyramidUndo {
+    /// The top two cards of the discard pile were taken from these places, in order.
+    Pair(Place, Place),
+    King(Place),
+    /// The top of the stock was turned over onto the waste.
+    Draw,
+    /// The waste was turned back over into the stock.
+    Redeal,
+    /// The action did nothing.
+    Nothing
+}
+
+impl Problem<PyramidAction, PyramidPercept> for Pyramid {
+    type Undo = PyramidUndo;
+    fn percept(&self) -> PyramidPercept {
+        PyramidPercept {
+            rows: self.rows.iter().map(|row| row.iter().map(|x| x.as_ref().map(|card| (card.ident(), card.clone()))).collect()).collect(),
+            stock: self.stock.iter().map(Card::ident).collect(),
+            waste: self.waste.iter().map(|card| (card.ident(), card.clone())).collect(),
+            discard: self.discard.iter().map(|card| (card.ident(), card.clone())).collect()
+        }
+    }
+    fn actions(&self) -> Vec<PyramidAction> {
+        let mut out = Vec::new();
+        let free = self.free();
+        for (i, card) in free.iter().enumerate() {
+            if card.rank == Rank::King {
+                out.push(PyramidAction::King((*card).clone()));
+            }
+            for other in &free[i+1..] {
+                if card.rank.value() + other.rank.value() == 13 {
+                    out.push(PyramidAction::pair((*card).clone(), (*other).clone()));
+                }
+            }
+        }
+        if !self.stock.is_empty() || (!self.waste.is_empty() && self.can_redeal()) {
+            out.push(PyramidAction::Draw);
+        }
+        out
+    }
+    fn result(&self, act: PyramidAction) -> Self {
+        let mut out = self.clone();
+        out.apply(act);
+        out
+    }
+    fn apply(&mut self, act: PyramidAction) -> PyramidUndo {
+        match act {
+            PyramidAction::Pair(a, b) => {
+                match (self.place(&a), self.place(&b)) {
+                    (Some(x), Some(y)) if x != y => {
+                        self.take(x);
+                        self.take(y);
+                        PyramidUndo::Pair(x, y)
+                    },
+                    _ => PyramidUndo::Nothing
+                }
+            },
+            PyramidAction::King(card) => {
+                match self.place(&card) {
+                    Some(x) => {
+                        self.take(x);
+                        PyramidUndo::King(x)
+                    },
+                    None => PyramidUndo::Nothing
+                }
+            },
+            PyramidAction::Draw => {
+                if let Some(card) = self.stock.pop() {
+                    self.waste.push(card);
+                    PyramidUndo::Draw
+                } else if self.can_redeal() {
+                    while let Some(card) = self.waste.pop() {
+                        self.stock.push(card);
+                    }
+                    self.redeals += 1;
+                    PyramidUndo::Redeal
+                } else {
+                    PyramidUndo::Nothing
+                }
+            }
+        }
+    }
+    fn undo(&mut self, undo: PyramidUndo) {
+        match undo {
+            PyramidUndo::Pair(x, y) => {
+                self.put_back(y);
+                self.put_back(x);
+            },
+            PyramidUndo::King(x) => self.put_back(x),
+            PyramidUndo::Draw => {
+                let card = self.waste.pop().unwrap();
+                self.stock.push(card);
+            },
+            PyramidUndo::Redeal => {
+                while let Some(card) = self.stock.pop() {
+                    self.waste.push(card);
+                }
+                self.redeals -= 1;
+            },
+            PyramidUndo::Nothing => ()
+        }
+    }
+    fn is_goal(&self) -> bool {
+        self.rows.iter().all(|row| row.iter().all(|x| x.is_none()))
+    }
+}
+
+impl Search<PyramidAction> for Pyramid {
+    type Key = Pyramid;
+    fn key(&self) -> Pyramid {
+        // the discard pile follows from the rest, and the pass count only matters with a limit
+        let mut key = self.clone();
+        key.discard.clear();
+        if key.passes.is_none() {
+            key.redeals = 0;
+        }
+        key
+    }
+    fn priority(&self, act: &PyramidAction) -> i32 {
+        // clearing the pyramid from the bottom up uncovers the most cards
+        let depth = |card: &Card| self.row_of(card).map_or(0, |r| r as i32 * 5 + 20);
+        match act {
+            PyramidAction::King(card) => 100 + depth(card),
+            PyramidAction::Pair(a, b) => depth(a) + depth(b),
+            &PyramidAction::Draw => 0
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PyramidPercept {
+    /// The pyramid from the top down, with a gap for every card already cleared.
+    pub rows: Vec<Vec<Option<(Ident, Card)>>>,
+    pub stock: Vec<Ident>,
+    pub waste: Vec<(Ident, Card)>,
+    pub discard: Vec<(Ident, Card)>
+}
+
+impl PyramidPercept {
+    /// The card shown at `id`, if it is face up.
+    fn card_at(&self, id: &(StackId, Option<usize>)) -> Option<&Card> {
+        match *id {
+            (StackId(1, r), Some(c)) => self.rows.get(r).and_then(|row| row.get(c)).and_then(|x| x.as_ref()).map(|x| &x.1),
+            (StackId(0, 1), Some(i)) => self.waste.get(i).map(|x| &x.1),
+            _ => None
+        }
+    }
+    fn locate(&self, card: &Card) -> Option<(StackId, Option<usize>)> {
+        for (r, row) in self.rows.iter().enumerate() {
+            if let Some(c) = row.iter().position(|x| x.as_ref().map(|x| &x.1) == Some(card)) {
+                return Some((StackId(1, r), Some(c)));
+            }
+        }
+        match self.waste.last() {
+            Some((_, top)) if top == card => Some((StackId(0, 1), Some(self.waste.len()-1))),
+            _ => None
+        }
+    }
+}
+
+fn get_stack_location(&StackId(i, j): &StackId) -> GridLocation {
+    match (i, j) {
+        (1, r) => GridLocation::new(GridValue(6 - r as i32, 0), GridValue(r as i32, 0), r as i32),
+        (0, 0) => GridLocation::new(GridValue(16, 0), GridValue(0, 0), 0),
+        (0, _) => GridLocation::new(GridValue(16, 0), GridValue(3, 0), 0),
+        _ => GridLocation::new(GridValue(16, 0), GridValue(7, 0), 0),
+    }
+}
+
+impl Renderable for PyramidPercept {
+    type CardId = (StackId, Option<usize>);
+    type Action = PyramidAction;
+    fn get_cards(&self) -> Vec<Self::CardId> {
+        let mut out = Vec::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            for (c, card) in row.iter().enumerate() {
+                if card.is_some() {
+                    out.push((StackId(1, r), Some(c)));
+                }
+            }
+        }
+        for &(ref stack, len) in &[(StackId(0,0), self.stock.len()), (StackId(0,1), self.waste.len()), (StackId(2,0), self.discard.len())] {
+            out.push((stack.clone(), None));
+            for i in 0..len {
+                out.push((stack.clone(), Some(i)));
+            }
+        }
+        out
+    }
+    fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>> {
+        let idx = match id.1 {
+            Some(idx) => idx,
+            None => return Some(CardData {
+                ident: Ident::new((id.0).0*16 + (id.0).1).interleave(),
+                pos: get_stack_location(&id.0) - GridLocation::new(GridValue(0,0), GridValue(0,0), 1),
+                display: CardDisplay::Empty,
+                drag_children: None
+            })
+        };
+        let (ident, display, free) = match id.0 {
+            StackId(1, r) => match self.rows.get(r).and_then(|row| row.get(idx)) {
+                Some(&Some((ref ident, ref card))) => (ident.clone(), CardDisplay::Front(card.clone()), !covered(&self.rows, r, idx)),
+                _ => return None
+            },
+            StackId(0, 0) => match self.stock.get(idx) {
+                Some(ident) => (ident.clone(), CardDisplay::Back, false),
+                None => return None
+            },
+            StackId(0, 1) => match self.waste.get(idx) {
+                Some((ident, card)) => (ident.clone(), CardDisplay::Front(card.clone()), idx + 1 == self.waste.len()),
+                None => return None
+            },
+            _ => match self.discard.get(idx) {
+                Some((ident, card)) => (ident.clone(), CardDisplay::Front(card.clone()), false),
+                None => return None
+            }
+        };
+        let mut offset = GridLocation::new(GridValue(0,0), GridValue(0,0), idx as i32);
+        if let StackId(1, _) = id.0 {
+            offset = GridLocation::new(GridValue(idx as i32*2, 0), GridValue(0,0), 0);
+        }
+        Some(CardData {
+            ident: ident.expand(),
+            pos: get_stack_location(&id.0) + offset,
+            display,
+            drag_children: if free { Some(Vec::new()) } else { None }
+        })
+    }
+    fn get_action_for(&self, act: MouseAction<Self::CardId>) -> Option<Self::Action> {
+        match act {
+            MouseAction::Drop(dragged, target) => {
+                match (self.card_at(&dragged), self.card_at(&target)) {
+                    (Some(a), Some(b)) => Some(PyramidAction::pair(a.clone(), b.clone())),
+                    _ => None
+                }
+            },
+            MouseAction::Tap((StackId(0, 0), _)) => Some(PyramidAction::Draw),
+            MouseAction::Tap(id) => self.card_at(&id).map(|card| PyramidAction::King(card.clone()))
+        }
+    }
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>> {
+        match act {
+            PyramidAction::Pair(a, b) => match (self.locate(a), self.locate(b)) {
+                (Some(a), Some(b)) => Some(MouseAction::Drop(a, b)),
+                _ => None
+            },
+            PyramidAction::King(card) => self.locate(card).map(MouseAction::Tap),
+            &PyramidAction::Draw => Some(MouseAction::Tap((StackId(0,0), self.stock.len().checked_sub(1))))
+        }
+    }
+    fn get_grid_extents() -> (GridLocation, GridLocation) {
+        (GridLocation::new(GridValue(-1,0), GridValue(-1,0), -1), GridLocation::new(GridValue(19,0), GridValue(10, 0), 20))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::problem::tests::walk;
+    use super::*;
+
+    #[test]
+    fn undo() {
+        let mut redealt = false;
+        for seed in 0..40 {
+            walk(Pyramid::deal_number(seed, Some(3)), seed, 300, |undo| redealt = redealt || *undo == PyramidUndo::Redeal);
+        }
+        assert!(redealt, "never redealt");
+    }
+}
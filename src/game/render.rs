@@ -27,5 +27,7 @@ pub trait Renderable {
     fn get_cards(&self) -> Vec<Self::CardId>;
     fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>>;
     fn get_action_for(&self, act: MouseAction<Self::CardId>) -> Option<Self::Action>;
+    /// The inverse of `get_action_for`: which cards the player would tap or drag to perform `act`.
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>>;
     fn get_grid_extents() -> (GridLocation, GridLocation);
 }
@@ -0,0 +1,379 @@
+use game::cards::{Card, Rank};
+
+/// Which cards can be built down on one another in the rows.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Building {
+    AlternateColors,
+    SameColor,
+    SameSuit,
+    AnySuit
+}
+
+impl Building {
+    /// Whether `card` may go on `under`, leaving their ranks aside. Jokers go with anything.
+    pub fn allows(&self, card: &Card, under: &Card) -> bool {
+        if card.is_joker() || under.is_joker() {
+            return true;
+        }
+        match *self {
+            Building::AlternateColors => card.color() != under.color(),
+            Building::SameColor => card.color() == under.color(),
+            Building::SameSuit => card.suit == under.suit,
+            Building::AnySuit => true
+        }
+    }
+    fn name(&self) -> &'static str {
+        match *self {
+            Building::AlternateColors => "alternate",
+            Building::SameColor => "color",
+            Building::SameSuit => "suit",
+            Building::AnySuit => "any"
+        }
+    }
+}
+
+/// Which face up cards in a row can be picked up along with everything on top of them.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Moving {
+    /// Any of them, in sequence or not.
+    Any,
+    /// Only ones with a run built by the building rule on top.
+    Built,
+    /// Only ones with a run in their own suit on top.
+    InSuit
+}
+
+impl Moving {
+    fn name(&self) -> &'static str {
+        match *self {
+            Moving::Any => "any",
+            Moving::Built => "built",
+            Moving::InSuit => "suit"
+        }
+    }
+}
+
+/// What can be moved into an empty row.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Empty {
+    /// A common house rule in Klondike, and the real rule in some other games.
+    AnyCard,
+    /// Only the rank the foundations finish with, which is the king unless they start from a
+    /// dealt card.
+    Kings
+}
+
+impl Empty {
+    fn name(&self) -> &'static str {
+        match *self {
+            Empty::AnyCard => "any",
+            Empty::Kings => "kings"
+        }
+    }
+}
+
+/// Which rank the foundations are built up from.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Foundation {
+    Aces,
+    /// The card dealt after the rows starts its foundation, and the others start from the same
+    /// rank.
+    Dealt
+}
+
+impl Foundation {
+    fn name(&self) -> &'static str {
+        match *self {
+            Foundation::Aces => "aces",
+            Foundation::Dealt => "dealt"
+        }
+    }
+}
+
+/// What tapping the deck does.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Stock {
+    /// Turns cards over onto the runoff, `draw` at a time, as often as `passes` allows.
+    Runoff,
+    /// Deals a card face up onto every row, once.
+    Rows
+}
+
+impl Stock {
+    fn name(&self) -> &'static str {
+        match *self {
+            Stock::Runoff => "runoff",
+            Stock::Rows => "rows"
+        }
+    }
+}
+
+/// How the rows are dealt.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Layout {
+    /// One card face up on each row, over one more face down card than the row before.
+    Klondike,
+    /// Four more face up cards on every row but the first, and nothing left over for a deck.
+    Yukon,
+    /// One card face up over two face down on every row.
+    Easthaven
+}
+
+impl Layout {
+    /// How many face down cards row `i` is dealt.
+    pub fn face_down(&self, i: usize) -> usize {
+        match *self {
+            Layout::Easthaven => 2,
+            _ => i
+        }
+    }
+    /// How many face up cards row `i` is dealt.
+    pub fn face_up(&self, i: usize) -> usize {
+        match *self {
+            Layout::Yukon if i > 0 => 5,
+            _ => 1
+        }
+    }
+    fn name(&self) -> &'static str {
+        match *self {
+            Layout::Klondike => "klondike",
+            Layout::Yukon => "yukon",
+            Layout::Easthaven => "easthaven"
+        }
+    }
+}
+
+/// Options that change how a game of solitaire plays, fixed when the cards are dealt.
+///
+/// Every game in the Klondike family is played by the same code; each is just a set of these.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct Rules {
+    /// How many cards each tap on the deck turns over onto the runoff.
+    pub draw: usize,
+    /// How many times the player may go through the deck, or `None` for no limit.
+    pub passes: Option<usize>,
+    pub build: Building,
+    /// Whether kings can be built on aces in the rows, and aces on kings in the foundations.
+    pub wrap: bool,
+    pub moves: Moving,
+    pub empty: Empty,
+    pub foundation: Foundation,
+    pub stock: Stock,
+    pub layout: Layout,
+    /// Whether the rows are dealt all face up.
+    pub open: bool,
+    /// How many jokers are shuffled in. They are wild in the rows, and are put away on any
+    /// foundation that has been started.
+    pub jokers: usize
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            draw: 3,
+            passes: None,
+            build: Building::AlternateColors,
+            wrap: false,
+            moves: Moving::Built,
+            empty: Empty::Kings,
+            foundation: Foundation::Aces,
+            stock: Stock::Runoff,
+            layout: Layout::Klondike,
+            open: false,
+            jokers: 0
+        }
+    }
+}
+
+/// Every game `Rules::named` knows.
+pub const GAMES: [&str; 7] = ["klondike", "thoughtful", "whitehead", "agnes", "easthaven", "yukon", "russian"];
+
+impl Rules {
+    /// The rules for a game in the Klondike family, by name.
+    ///
+    /// Agnes is Agnes Sorel: the foundations start from whatever card is dealt after the rows,
+    /// and the deck is dealt out across the rows seven cards at a time.
+    pub fn named(name: &str) -> Option<Rules> {
+        let klondike = Rules::default();
+        Some(match name {
+            "klondike" => klondike,
+            "thoughtful" => Rules {
+                open: true,
+                ..klondike
+            },
+            "whitehead" => Rules {
+                draw: 1,
+                passes: Some(1),
+                build: Building::SameColor,
+                moves: Moving::InSuit,
+                empty: Empty::AnyCard,
+                open: true,
+                ..klondike
+            },
+            "agnes" => Rules {
+                build: Building::SameColor,
+                wrap: true,
+                empty: Empty::Kings,
+                foundation: Foundation::Dealt,
+                stock: Stock::Rows,
+                ..klondike
+            },
+            "easthaven" => Rules {
+                empty: Empty::AnyCard,
+                stock: Stock::Rows,
+                layout: Layout::Easthaven,
+                ..klondike
+            },
+            "yukon" => Rules {
+                moves: Moving::Any,
+                layout: Layout::Yukon,
+                ..klondike
+            },
+            "russian" => Rules {
+                build: Building::SameSuit,
+                moves: Moving::Any,
+                layout: Layout::Yukon,
+                ..klondike
+            },
+            _ => return None
+        })
+    }
+    /// The name of the game these rules make, if it has one. The draw, the pass limit, what
+    /// goes in empty rows and the jokers are left to the player.
+    pub fn name(&self) -> Option<&'static str> {
+        GAMES.iter().cloned().find(|name| Rules::named(name).is_some_and(|rules| Rules {
+            draw: self.draw,
+            passes: self.passes,
+            empty: self.empty,
+            jokers: self.jokers,
+            ..rules
+        } == *self))
+    }
+    /// Whether what goes in empty rows has been changed from the named game's own rule.
+    pub fn house_empty(&self) -> bool {
+        self.name().and_then(Rules::named).is_some_and(|rules| rules.empty != self.empty)
+    }
+    /// Whether `card` can be built on `under` in the rows.
+    pub fn builds(&self, card: &Card, under: &Card) -> bool {
+        let wild = card.is_joker() || under.is_joker();
+        let ranked = wild || card.rank.next() == Some(under.rank) || (self.wrap && card.rank == Rank::King && under.rank == Rank::Ace);
+        ranked && self.build.allows(card, under)
+    }
+    /// Writes the rules out as space separated values, for save and replay files.
+    pub fn to_text(&self) -> String {
+        format!("{} {} {} {} {} {} {} {} {} {} {}", self.draw, match self.passes {
+            Some(x) => x.to_string(),
+            None => String::from("-")
+        }, self.build.name(), self.layout.name(), self.moves.name(), self.empty.name(), self.foundation.name(),
+            self.stock.name(), if self.open { "open" } else { "closed" }, if self.wrap { "wrap" } else { "nowrap" }, self.jokers)
+    }
+    /// Reads back rules written by `to_text`.
+    pub fn from_text(text: &str) -> Result<Rules, String> {
+        let mut out = Rules::default();
+        let mut words = text.split_whitespace();
+        let number = |word: Option<&str>| word.and_then(|x| x.parse::<usize>().ok()).ok_or(format!("bad rules '{}'", text));
+        let bad = |word: &str| Err(format!("bad rules '{}': unexpected '{}'", text, word));
+        out.draw = number(words.next())?;
+        out.passes = match words.next() {
+            Some("-") => None,
+            x => Some(number(x)?)
+        };
+        // files written before there was any choice in these are plain klondike, which let any
+        // card into an empty row back then
+        out.empty = Empty::AnyCard;
+        if let Some(word) = words.next() {
+            out.build = match word {
+                "alternate" => Building::AlternateColors,
+                "color" => Building::SameColor,
+                "suit" => Building::SameSuit,
+                "any" => Building::AnySuit,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.layout = match word {
+                "klondike" => Layout::Klondike,
+                "yukon" => Layout::Yukon,
+                "easthaven" => Layout::Easthaven,
+                _ => return bad(word)
+            };
+        }
+        if out.layout == Layout::Yukon {
+            // older yukon files left this to the layout
+            out.moves = Moving::Any;
+        }
+        if let Some(word) = words.next() {
+            out.moves = match word {
+                "any" => Moving::Any,
+                "built" => Moving::Built,
+                "suit" => Moving::InSuit,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.empty = match word {
+                "any" => Empty::AnyCard,
+                "kings" => Empty::Kings,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.foundation = match word {
+                "aces" => Foundation::Aces,
+                "dealt" => Foundation::Dealt,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.stock = match word {
+                "runoff" => Stock::Runoff,
+                "rows" => Stock::Rows,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.open = match word {
+                "open" => true,
+                "closed" => false,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.wrap = match word {
+                "wrap" => true,
+                "nowrap" => false,
+                _ => return bad(word)
+            };
+        }
+        if let Some(word) = words.next() {
+            out.jokers = number(Some(word))?;
+        }
+        if out.draw == 0 {
+            return Err(String::from("the deck must draw at least one card"));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_rule_round_trips() {
+        for &empty in &[Empty::Kings, Empty::AnyCard] {
+            let rules = Rules { empty, ..Rules::default() };
+            assert_eq!(Rules::from_text(&rules.to_text()), Ok(rules));
+        }
+    }
+
+    #[test]
+    fn old_rules_let_anything_into_empty_rows() {
+        // the draw and pass limit were all there was to write down at first
+        let rules = Rules::from_text("3 -").unwrap();
+        assert_eq!(rules.empty, Empty::AnyCard);
+        assert_eq!(rules.draw, 3);
+        assert_eq!(rules.passes, None);
+        assert_eq!(Rules::from_text("1 3").unwrap().empty, Empty::AnyCard);
+    }
+}
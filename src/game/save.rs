@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use game::cards::Card;
+use game::history::History;
+use game::problem::Problem;
+use game::solitaire::{Solitaire, CardGameAction, StackId};
+
+/// Bumped whenever the save format changes in a way older versions can't read. Version 2
+/// added the move, empty row and foundation rules (and more since) to the rules line;
+/// version 1 files still load, with the rules they were played under back then.
+pub const SAVE_VERSION: u32 = 2;
+
+/// A game in progress: how it was dealt, the moves made since, and the time spent on it.
+pub struct Save {
+    pub seed: u64,
+    /// For Vegas games, how many foundation cards have been paid out for.
+    pub vegas: Option<usize>,
+    pub elapsed: Duration,
+    pub history: History<Solitaire, CardGameAction>
+}
+
+fn action_str(act: &CardGameAction) -> String {
+    match *act {
+        CardGameAction::Move(ref card, StackId(i, j)) => format!("move {} {} {}", card.code(), i, j),
+        CardGameAction::Tap(StackId(i, j)) => format!("tap {} {}", i, j)
+    }
+}
+
+fn parse_action(text: &str) -> Option<CardGameAction> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let stack = |i: &str, j: &str| match (i.parse(), j.parse()) {
+        (Ok(i), Ok(j)) => Some(StackId(i, j)),
+        _ => None
+    };
+    match (words.first(), words.len()) {
+        (Some(&"move"), 4) => Card::from_code(words[1]).and_then(|card| stack(words[2], words[3]).map(|stack| CardGameAction::Move(card, stack))),
+        (Some(&"tap"), 3) => stack(words[1], words[2]).map(CardGameAction::Tap),
+        _ => None
+    }
+}
+
+impl Save {
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            ::std::fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(path)?;
+        writeln!(file, "solitaire save {}", SAVE_VERSION)?;
+        writeln!(file, "seed {}", self.seed)?;
+        match self.vegas {
+            Some(paid) => writeln!(file, "vegas {}", paid)?,
+            None => writeln!(file, "vegas -")?
+        }
+        writeln!(file, "elapsed {}", self.elapsed.as_secs())?;
+        for line in self.history.start().to_text() {
+            writeln!(file, "{}", line)?;
+        }
+        for step in self.history.steps() {
+            let acts: Vec<String> = step.into_iter().map(action_str).collect();
+            writeln!(file, "step {}", acts.join(", "))?;
+        }
+        Ok(())
+    }
+    /// Loads a save written by `write`. Every move is replayed from the starting deal, so a
+    /// file that has been tampered with or damaged is caught rather than producing an
+    /// impossible game.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Save, String> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path).and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Save::parse(&text).map_err(|e| format!("{} is not a usable save file: {}", path.display(), e))
+    }
+    fn parse(text: &str) -> Result<Save, String> {
+        let mut lines = text.lines();
+        match lines.next().map(|x| x.split_whitespace().collect::<Vec<_>>()) {
+            Some(ref words) if words.len() == 3 && words[0] == "solitaire" && words[1] == "save" => {
+                if !words[2].parse::<u32>().map(|x| (1..=SAVE_VERSION).contains(&x)).unwrap_or(false) {
+                    return Err(format!("it was written by an incompatible version (format {}, expected {})", words[2], SAVE_VERSION));
+                }
+            },
+            _ => return Err(String::from("it does not start with a save header"))
+        }
+        let (seed, vegas, elapsed) = {
+            let mut field = |name: &str| -> Result<String, String> {
+                match lines.next().map(|x| x.splitn(2, ' ').collect::<Vec<_>>()) {
+                    Some(ref words) if words.len() == 2 && words[0] == name => Ok(words[1].to_string()),
+                    _ => Err(format!("missing {}", name))
+                }
+            };
+            (field("seed")?.parse().map_err(|_| String::from("bad seed"))?,
+             match field("vegas")?.as_str() {
+                 "-" => None,
+                 x => Some(x.parse().map_err(|_| String::from("bad vegas payout"))?)
+             },
+             field("elapsed")?.parse().map_err(|_| String::from("bad elapsed time"))?)
+        };
+        let rest: Vec<&str> = lines.collect();
+        let split = rest.iter().position(|x| x.starts_with("step")).unwrap_or(rest.len());
+        let mut history = History::new(Solitaire::from_text(&rest[..split])?);
+        for line in &rest[split..] {
+            if !line.starts_with("step ") {
+                return Err(format!("unexpected line '{}'", line));
+            }
+            history.begin();
+            for text in line[5..].split(',') {
+                let act = parse_action(text).ok_or(format!("bad move '{}'", text.trim()))?;
+                if !history.current().actions().contains(&act) {
+                    return Err(format!("illegal move '{}'", text.trim()));
+                }
+                history.apply(act);
+            }
+            history.commit();
+        }
+        Ok(Save {
+            seed,
+            vegas,
+            elapsed: Duration::from_secs(elapsed),
+            history
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::rules::{Rules, Empty};
+
+    fn file(version: u32, rules: &str) -> String {
+        let game = Solitaire::deal_number(1, Rules::default());
+        let act = action_str(&game.actions()[0]);
+        let mut lines = vec![format!("solitaire save {}", version), String::from("seed 1"), String::from("vegas -"), String::from("elapsed 30")];
+        for line in game.to_text() {
+            lines.push(if line.starts_with("rules ") { format!("rules {}", rules) } else { line });
+        }
+        lines.push(format!("step {}", act));
+        lines.join("\n")
+    }
+
+    #[test]
+    fn loads_version_1() {
+        let save = Save::parse(&file(1, "3 -")).unwrap();
+        assert_eq!(save.seed, 1);
+        assert_eq!(save.elapsed, Duration::from_secs(30));
+        assert_eq!(save.history.steps().len(), 1);
+        assert_eq!(save.history.start().rules().empty, Empty::AnyCard);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let rules = Rules::default().to_text();
+        assert!(Save::parse(&file(SAVE_VERSION, &rules)).is_ok());
+        assert!(Save::parse(&file(SAVE_VERSION + 1, &rules)).is_err());
+        assert!(Save::parse(&file(0, &rules)).is_err());
+    }
+}
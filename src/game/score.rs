@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use game::history::History;
+use game::solitaire::{Solitaire, CardGameAction, StackId};
+
+/// What each Vegas deal costs.
+pub const VEGAS_ANTE: i64 = 52;
+/// What each card on the foundations pays back at the end of a Vegas game.
+pub const VEGAS_PAYOUT: i64 = 5;
+
+/// A Vegas scoring bank, kept in a file so the running total carries over between games.
+pub struct Vegas {
+    pub bank: i64,
+    /// How many foundation cards have already been paid out for this game.
+    pub paid: usize,
+    path: PathBuf
+}
+
+impl Vegas {
+    /// Loads the bank stored at `path`, starting from nothing if there isn't one yet, for a game
+    /// that has already been paid out for `paid` cards.
+    pub fn load<P: AsRef<Path>>(path: P, paid: usize) -> io::Result<Vegas> {
+        let path = path.as_ref().to_path_buf();
+        let bank = match File::open(&path) {
+            Ok(mut file) => {
+                let mut text = String::new();
+                file.read_to_string(&mut text)?;
+                text.trim().parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{} does not hold a Vegas score", path.display())))?
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e)
+        };
+        Ok(Vegas {
+            bank,
+            paid,
+            path
+        })
+    }
+    /// Loads the bank and pays for a new deal. The ante is written back straight away so
+    /// quitting can't dodge it.
+    pub fn ante<P: AsRef<Path>>(path: P) -> io::Result<Vegas> {
+        let mut vegas = Vegas::load(path, 0)?;
+        vegas.bank -= VEGAS_ANTE;
+        vegas.store()?;
+        Ok(vegas)
+    }
+    /// Pays out for the cards `state` has on the foundations that haven't been paid for yet,
+    /// and stores the new bank. Returns what the game has won or lost overall.
+    pub fn settle(&mut self, state: &Solitaire) -> io::Result<i64> {
+        let count = state.foundation_count();
+        self.bank += (count as i64 - self.paid as i64) * VEGAS_PAYOUT;
+        self.paid = count;
+        self.store()?;
+        Ok(count as i64 * VEGAS_PAYOUT - VEGAS_ANTE)
+    }
+    fn store(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            ::std::fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&self.path)?;
+        writeln!(file, "{}", self.bank)
+    }
+}
+
+/// Standard scoring: points for progress made by each move, tallied alongside the move count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Score {
+    pub points: i32,
+    pub moves: usize
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Score {
+    pub fn new() -> Score {
+        Score {
+            points: 0,
+            moves: 0
+        }
+    }
+    /// Scores every move in `history` that hasn't been undone.
+    pub fn tally(history: &History<Solitaire, CardGameAction>) -> Score {
+        let mut score = Score::new();
+        for (before, act, after) in history.transitions() {
+            score.record(before, act, after);
+        }
+        score
+    }
+    /// Scores `act`, which took the game from `before` to `after`.
+    pub fn record(&mut self, before: &Solitaire, act: &CardGameAction, after: &Solitaire) {
+        self.moves += 1;
+        if let CardGameAction::Move(card, to) = act {
+            let from = before.locate(card).map(|(stack, _)| stack);
+            self.points += match (from, to) {
+                (Some(StackId(0, 1)), &StackId(1, _)) => 5,
+                (Some(StackId(0, 1)), &StackId(2, _)) => 10,
+                (Some(StackId(1, _)), &StackId(2, _)) => 10,
+                (Some(StackId(2, _)), &StackId(1, _)) => -15,
+                _ => 0
+            };
+        }
+        if after.face_down() < before.face_down() {
+            self.points += 5*(before.face_down() - after.face_down()) as i32;
+        }
+        if after.redeals() > before.redeals() {
+            if after.rules().draw == 1 {
+                self.points -= 100;
+            } else if after.redeals() > 3 {
+                self.points -= 20;
+            }
+        }
+    }
+    /// The score to show after `elapsed` time has passed: two points are lost every ten seconds,
+    /// and a won game earns a bonus for finishing quickly.
+    pub fn total(&self, elapsed: Duration, won: bool) -> i32 {
+        let secs = elapsed.as_secs();
+        let mut total = self.points - 2*(secs/10) as i32;
+        if won && secs > 30 {
+            total += (700000/secs) as i32;
+        }
+        if total < 0 {
+            0
+        } else {
+            total
+        }
+    }
+}
+
+/// A game timer that can start from time already spent on an earlier sitting.
+pub struct Clock {
+    base: Duration,
+    started: Instant
+}
+
+impl Clock {
+    pub fn new(base: Duration) -> Clock {
+        Clock {
+            base,
+            started: Instant::now()
+        }
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.base + self.started.elapsed()
+    }
+}
+
+/// Formats a duration as minutes and seconds.
+pub fn clock_str(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}", secs/60, secs%60)
+}
@@ -0,0 +1,379 @@
+//! The engine behind Golf and TriPeaks, where every card is played onto the waste one rank above
+//! or below the card on top of it. The games differ only in how the cards are laid out, which
+//! `Shape` describes, and in whether an ace and a king count as next to each other.
+use game::cards::*;
+use game::problem::Problem;
+use game::render::*;
+use game::grid::*;
+use game::history::History;
+use game::solitaire::StackId;
+use game::solver::Search;
+use game::deal;
+
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum Shape {
+    /// Seven columns of five cards, all face up.
+    Golf,
+    /// Three overlapping peaks of face down cards above a row of ten face up ones.
+    TriPeaks
+}
+
+impl Shape {
+    /// How many cards are laid out.
+    pub fn size(&self) -> usize {
+        match *self {
+            Shape::Golf => 35,
+            Shape::TriPeaks => 28
+        }
+    }
+    /// Whether an ace can be played on a king and a king on an ace.
+    pub fn wraps(&self) -> bool {
+        *self == Shape::TriPeaks
+    }
+    /// Whether cards stay face down until nothing overlaps them.
+    pub fn hides(&self) -> bool {
+        *self == Shape::TriPeaks
+    }
+    /// The slots holding slot `i` down.
+    pub fn covering(&self, i: usize) -> Vec<usize> {
+        match *self {
+            Shape::Golf => if i % 5 < 4 { vec![i+1] } else { Vec::new() },
+            Shape::TriPeaks => match i {
+                i if i < 3 => vec![3 + i*2, 4 + i*2],
+                i if i < 9 => {
+                    let (peak, j) = ((i-3) / 2, (i-3) % 2);
+                    vec![9 + peak*3 + j, 10 + peak*3 + j]
+                },
+                i if i < 18 => vec![i+9, i+10],
+                _ => Vec::new()
+            }
+        }
+    }
+    /// The slots at the very top of each peak; clearing one earns a bonus in TriPeaks.
+    pub fn peaks(&self) -> Vec<usize> {
+        match *self {
+            Shape::Golf => Vec::new(),
+            Shape::TriPeaks => vec![0, 1, 2]
+        }
+    }
+    /// Where slot `i` is laid out.
+    pub fn location(&self, i: usize) -> GridLocation {
+        match *self {
+            Shape::Golf => GridLocation::new(GridValue((i/5) as i32*2, 0), GridValue(0, (i%5) as i32*2), (i%5) as i32),
+            Shape::TriPeaks => {
+                let (x, y) = match i {
+                    i if i < 3 => (i*6 + 3, 0),
+                    i if i < 9 => (((i-3) / 2)*6 + ((i-3) % 2)*2 + 2, 1),
+                    i if i < 18 => ((i-9)*2 + 1, 2),
+                    _ => ((i-18)*2, 3)
+                };
+                GridLocation::new(GridValue(x as i32, 0), GridValue(y, 0), y)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Sequence {
+    shape: Shape,
+    tableau: Vec<Option<Card>>,
+    stock: Vec<Card>,
+    waste: Vec<Card>
+}
+
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub enum SequenceAction {
+    /// Plays a card from the layout onto the waste.
+    Play(Card),
+    /// Turns the top of the stock over onto the waste.
+    Draw
+}
+
+impl Sequence {
+    /// Lays out `shape` from the top of `cards` and starts the waste with the next one. The rest
+    /// make up the stock.
+    pub fn deal(mut cards: Vec<Card>, shape: Shape) -> Self {
+        let mut tableau = Vec::new();
+        for _ in 0..shape.size() {
+            tableau.push(cards.pop());
+        }
+        let waste = cards.pop().into_iter().collect();
+        Sequence {
+            shape,
+            tableau,
+            stock: cards,
+            waste
+        }
+    }
+    pub fn deal_number(seed: u64, shape: Shape) -> Self {
+        Sequence::deal(deal::shuffled(seed), shape)
+    }
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+    /// The number of cards still laid out.
+    pub fn left(&self) -> usize {
+        self.tableau.iter().filter(|x| x.is_some()).count()
+    }
+    /// The number of peaks cleared away.
+    pub fn peaks_cleared(&self) -> usize {
+        self.shape.peaks().into_iter().filter(|&i| self.tableau[i].is_none()).count()
+    }
+    fn free(&self, i: usize) -> bool {
+        self.shape.covering(i).into_iter().all(|j| self.tableau[j].is_none())
+    }
+    /// Whether `card` can go on the waste.
+    fn fits(&self, card: &Card) -> bool {
+        match self.waste.last() {
+            Some(top) => {
+                let ends = (card.rank, top.rank) == (Rank::Ace, Rank::King) || (card.rank, top.rank) == (Rank::King, Rank::Ace);
+                card.rank.next() == Some(top.rank) || top.rank.next() == Some(card.rank) || (self.shape.wraps() && ends)
+            },
+            None => true
+        }
+    }
+}
+
+/// What it takes to put a `Sequence` back the way it was before an action.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SequenceUndo {
+    /// The top of the waste was played from this slot.
+    Play(usize),
+    /// The top of the waste was drawn from the stock.
+    Draw,
+    /// The action did nothing.
+    Nothing
+}
+
+impl Problem<SequenceAction, SequencePercept> for Sequence {
+    type Undo = SequenceUndo;
+    fn percept(&self) -> SequencePercept {
+        SequencePercept {
+            shape: self.shape,
+            tableau: self.tableau.iter().enumerate().map(|(i, x)| x.as_ref().map(|card| {
+                (card.ident(), if self.shape.hides() && !self.free(i) { None } else { Some(card.clone()) })
+            })).collect(),
+            stock: self.stock.iter().map(Card::ident).collect(),
+            waste: self.waste.iter().map(|card| (card.ident(), card.clone())).collect()
+        }
+    }
+    fn actions(&self) -> Vec<SequenceAction> {
+        let mut out = Vec::new();
+        for (i, card) in self.tableau.iter().enumerate() {
+            if let Some(card) = card {
+                if self.free(i) && self.fits(card) {
+                    out.push(SequenceAction::Play(card.clone()));
+                }
+            }
+        }
+        if !self.stock.is_empty() {
+            out.push(SequenceAction::Draw);
+        }
+        out
+    }
+    fn result(&self, act: SequenceAction) -> Self {
+        let mut out = self.clone();
+        out.apply(act);
+        out
+    }
+    fn apply(&mut self, act: SequenceAction) -> SequenceUndo {
+        match act {
+            SequenceAction::Play(card) => {
+                match self.tableau.iter().position(|x| x.as_ref() == Some(&card)) {
+                    Some(i) => {
+                        let card = self.tableau[i].take().unwrap();
+                        self.waste.push(card);
+                        SequenceUndo::Play(i)
+                    },
+                    None => SequenceUndo::Nothing
+                }
+            },
+            SequenceAction::Draw => {
+                match self.stock.pop() {
+                    Some(card) => {
+                        self.waste.push(card);
+                        SequenceUndo::Draw
+                    },
+                    None => SequenceUndo::Nothing
+                }
+            }
+        }
+    }
+    fn undo(&mut self, undo: SequenceUndo) {
+        match undo {
+            SequenceUndo::Play(i) => self.tableau[i] = self.waste.pop(),
+            SequenceUndo::Draw => {
+                let card = self.waste.pop().unwrap();
+                self.stock.push(card);
+            },
+            SequenceUndo::Nothing => ()
+        }
+    }
+    fn is_goal(&self) -> bool {
+        self.tableau.iter().all(|x| x.is_none())
+    }
+}
+
+impl Search<SequenceAction> for Sequence {
+    /// Nothing under the top of the waste can be played again, and the stock is only ever
+    /// taken from the top, so its size says all there is to say about it.
+    type Key = (Vec<Option<Card>>, usize, Option<Card>);
+    fn key(&self) -> Self::Key {
+        (self.tableau.clone(), self.stock.len(), self.waste.last().cloned())
+    }
+    fn priority(&self, act: &SequenceAction) -> i32 {
+        match act {
+            // prefer cards holding others down
+            SequenceAction::Play(card) => {
+                let held = self.tableau.iter().position(|x| x.as_ref() == Some(card)).map_or(0, |i| {
+                    (0..i).filter(|&j| self.shape.covering(j).contains(&i)).count()
+                });
+                10 + held as i32
+            },
+            &SequenceAction::Draw => 0
+        }
+    }
+}
+
+/// Scores a game of TriPeaks: every card played from the layout is worth one more point than the
+/// last, until a card is drawn from the stock. Clearing a peak is worth 15, or 30 for the last
+/// one.
+pub fn streak_score(history: &History<Sequence, SequenceAction>) -> (i32, usize) {
+    let mut points = 0;
+    let mut streak = 0;
+    for (before, act, after) in history.transitions() {
+        match *act {
+            SequenceAction::Play(_) => {
+                streak += 1;
+                points += streak as i32;
+            },
+            SequenceAction::Draw => streak = 0
+        }
+        if after.peaks_cleared() > before.peaks_cleared() {
+            points += if after.peaks_cleared() == after.shape.peaks().len() { 30 } else { 15 };
+        }
+    }
+    (points, streak)
+}
+
+/// The stock and waste sit below the layout in both games.
+fn get_stack_location(&StackId(_, j): &StackId) -> GridLocation {
+    GridLocation::new(GridValue(j as i32*4, 0), GridValue(6, 0), 0)
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SequencePercept {
+    pub shape: Shape,
+    /// The cards laid out, with a gap for every card played and face down cards left blank.
+    pub tableau: Vec<Option<(Ident, Option<Card>)>>,
+    pub stock: Vec<Ident>,
+    pub waste: Vec<(Ident, Card)>
+}
+
+impl SequencePercept {
+    fn card_at(&self, id: &(StackId, Option<usize>)) -> Option<&Card> {
+        match id {
+            &(StackId(1, 0), Some(i)) => self.tableau.get(i).and_then(|x| x.as_ref()).and_then(|x| x.1.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl Renderable for SequencePercept {
+    type CardId = (StackId, Option<usize>);
+    type Action = SequenceAction;
+    fn get_cards(&self) -> Vec<Self::CardId> {
+        let mut out = Vec::new();
+        for (i, slot) in self.tableau.iter().enumerate() {
+            if slot.is_some() {
+                out.push((StackId(1, 0), Some(i)));
+            }
+        }
+        for &(ref stack, len) in &[(StackId(0,0), self.stock.len()), (StackId(0,1), self.waste.len())] {
+            out.push((stack.clone(), None));
+            for i in 0..len {
+                out.push((stack.clone(), Some(i)));
+            }
+        }
+        out
+    }
+    fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>> {
+        let idx = match id.1 {
+            Some(idx) => idx,
+            None => return Some(CardData {
+                ident: Ident::new((id.0).0*16 + (id.0).1).interleave(),
+                pos: get_stack_location(&id.0) - GridLocation::new(GridValue(0,0), GridValue(0,0), 1),
+                display: CardDisplay::Empty,
+                drag_children: None
+            })
+        };
+        let (ident, display, pos) = match id.0 {
+            StackId(1, _) => match self.tableau.get(idx) {
+                Some(&Some((ref ident, ref card))) => {
+                    let display = match card {
+                        Some(card) => CardDisplay::Front(card.clone()),
+                        &None => CardDisplay::Back
+                    };
+                    (ident.clone(), display, self.shape.location(idx))
+                },
+                _ => return None
+            },
+            StackId(0, 0) => match self.stock.get(idx) {
+                Some(ident) => (ident.clone(), CardDisplay::Back, get_stack_location(&id.0)),
+                None => return None
+            },
+            _ => match self.waste.get(idx) {
+                Some((ident, card)) => (ident.clone(), CardDisplay::Front(card.clone()), get_stack_location(&id.0)),
+                None => return None
+            }
+        };
+        let draggable = match display {
+            CardDisplay::Front(_) => (id.0).0 == 1,
+            _ => false
+        };
+        Some(CardData {
+            ident: ident.expand(),
+            pos: pos + GridLocation::new(GridValue(0,0), GridValue(0,0), if (id.0).0 == 1 { 0 } else { idx as i32 }),
+            display,
+            drag_children: if draggable { Some(Vec::new()) } else { None }
+        })
+    }
+    fn get_action_for(&self, act: MouseAction<Self::CardId>) -> Option<Self::Action> {
+        match act {
+            MouseAction::Drop(dragged, (StackId(0, 1), _)) | MouseAction::Tap(dragged) => {
+                if dragged.0 == StackId(0, 0) {
+                    Some(SequenceAction::Draw)
+                } else {
+                    self.card_at(&dragged).map(|card| SequenceAction::Play(card.clone()))
+                }
+            },
+            _ => None
+        }
+    }
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>> {
+        match act {
+            SequenceAction::Play(card) => {
+                self.tableau.iter().position(|x| x.as_ref().and_then(|x| x.1.as_ref()) == Some(card))
+                    .map(|i| MouseAction::Tap((StackId(1, 0), Some(i))))
+            },
+            &SequenceAction::Draw => Some(MouseAction::Tap((StackId(0,0), self.stock.len().checked_sub(1))))
+        }
+    }
+    fn get_grid_extents() -> (GridLocation, GridLocation) {
+        (GridLocation::new(GridValue(-1,0), GridValue(-1,0), -1), GridLocation::new(GridValue(19,0), GridValue(8, 0), 20))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::problem::tests::walk;
+    use super::*;
+
+    #[test]
+    fn undo() {
+        for seed in 0..40 {
+            walk(Sequence::deal_number(seed, Shape::Golf), seed, 100, |_| ());
+            walk(Sequence::deal_number(seed, Shape::TriPeaks), seed, 100, |_| ());
+        }
+    }
+}
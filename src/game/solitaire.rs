@@ -1,27 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 pub use game::cards::*;
 use game::problem::Problem;
+use game::solver::Search;
 use game::render::*;
 use game::grid::*;
+use game::rules::{Rules, Building, Moving, Empty, Foundation, Stock};
+use game::deal;
 
-impl Card {
-    pub fn new(suit: usize, color:usize, rank: usize) -> Card {
-        Card {
-            suit: suit,
-            color: color,
-            rank: rank
-        }
-    }
-}
-
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Hash)]
 pub struct Solitaire {
     deck: Vec<Card>,
     runoff: Vec<Card>,
     row: Vec<Vec<Card>>,
     visibility: Vec<usize>,
-    goal: Vec<Vec<Card>>
+    goal: Vec<Vec<Card>>,
+    /// The rank every foundation starts from.
+    base: Rank,
+    redeals: usize,
+    rules: Rules
 }
 
 impl Clone for Solitaire {
@@ -31,37 +28,186 @@ impl Clone for Solitaire {
             runoff: self.runoff.clone(),
             row: self.row.clone(),
             visibility: self.visibility.clone(),
-            goal: self.goal.clone() 
+            goal: self.goal.clone(),
+            base: self.base,
+            redeals: self.redeals,
+            rules: self.rules.clone()
         }
     }
 }
 
 impl Solitaire {
-    pub fn deal(mut cards: Vec<Card>, row_count: usize, suits: usize) -> Self {
+    /// Deals `row_count` rows from the top of `cards`, laid out the way `rules` say. The rest
+    /// make up the deck.
+    pub fn deal(mut cards: Vec<Card>, row_count: usize, suits: usize, rules: Rules) -> Self {
         let mut row = Vec::new();
         let mut visibility = Vec::new();
         for i in 0..row_count {
             let mut stack = Vec::new();
-            for j in 0..i+1 {
+            let hidden = rules.layout.face_down(i);
+            for _ in 0..hidden+rules.layout.face_up(i) {
                 stack.push(cards.pop().unwrap());
             }
             row.push(stack);
-            visibility.push(i);
+            visibility.push(if rules.open { 0 } else { hidden });
         }
         let mut goal = Vec::new();
-        for i in 0..suits {
+        for _i in 0..suits {
             goal.push(Vec::new());
         }
+        let mut base = Rank::Ace;
+        if rules.foundation == Foundation::Dealt {
+            // a joker can't start anything, so it goes to the bottom of the deck instead
+            while let Some(card) = cards.pop() {
+                if card.is_joker() {
+                    cards.insert(0, card);
+                    continue;
+                }
+                base = card.rank;
+                goal[0].push(card);
+                break;
+            }
+        }
         let mut deck = Vec::new();
         while let Some(card) = cards.pop() {
             deck.push(card);
         }
         Solitaire{
-            deck: deck,
+            deck,
             runoff: Vec::new(),
-            row: row,
-            visibility: visibility,
-            goal: goal
+            row,
+            visibility,
+            goal,
+            base,
+            redeals: 0,
+            rules
+        }
+    }
+    /// Whether the runoff may be turned back over into the deck once the deck runs out.
+    pub fn can_redeal(&self) -> bool {
+        match self.rules.passes {
+            Some(passes) => self.redeals + 1 < passes,
+            None => true
+        }
+    }
+    /// How many times the runoff has been turned back into the deck.
+    pub fn redeals(&self) -> usize {
+        self.redeals
+    }
+    /// The number of face down cards left in the rows.
+    pub fn face_down(&self) -> usize {
+        self.visibility.iter().sum()
+    }
+    /// The number of cards on the foundations, not counting jokers, so a full deck always
+    /// comes to 52 however many jokers were dealt.
+    pub fn foundation_count(&self) -> usize {
+        self.goal.iter().map(|x| x.iter().filter(|card| !card.is_joker()).count()).sum()
+    }
+    /// Deals the standard game for a deal number.
+    pub fn deal_number(seed: u64, rules: Rules) -> Self {
+        let cards = deal::Deck {
+            jokers: rules.jokers,
+            ..deal::Deck::standard()
+        }.shuffled(seed);
+        Solitaire::deal(cards, 7, 4, rules)
+    }
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+    /// The rank every foundation starts from.
+    pub fn base(&self) -> Rank {
+        self.base
+    }
+    /// The number of rows and the number of foundations.
+    pub fn size(&self) -> (usize, usize) {
+        (self.row.len(), self.goal.len())
+    }
+    /// The cards in a stack, bottom first.
+    pub fn stack(&self, id: &StackId) -> Option<&Vec<Card>> {
+        match *id {
+            StackId(0,0) => Some(&self.deck),
+            StackId(0,1) => Some(&self.runoff),
+            StackId(1,x) => self.row.get(x),
+            StackId(2,x) => self.goal.get(x),
+            _ => None
+        }
+    }
+    /// How many cards at the bottom of row `i` are face down.
+    pub fn hidden(&self, i: usize) -> usize {
+        self.visibility[i]
+    }
+    /// Puts a position back together from its stacks. Each row comes with the number of cards at
+    /// the bottom of it that are face down.
+    pub fn from_stacks(deck: Vec<Card>, runoff: Vec<Card>, rows: Vec<(usize, Vec<Card>)>, goal: Vec<Vec<Card>>, base: Rank, redeals: usize, rules: Rules) -> Solitaire {
+        let (visibility, row) = rows.into_iter().unzip();
+        Solitaire {
+            deck,
+            runoff,
+            row,
+            visibility,
+            goal,
+            base,
+            redeals,
+            rules
+        }
+    }
+    /// The position as far as the rest of the game is concerned, so that positions which only
+    /// differ in ways that can't matter come out the same:
+    ///
+    /// * the rows are sorted, so it makes no difference which empty row a king went to, unless
+    ///   the deck is still to be dealt onto the rows in order;
+    /// * the foundations are sorted by suit, so neither does which one a suit claimed;
+    /// * face down cards never move until they are turned over, so a pile of them is told apart
+    ///   by its bottom card and height alone, which is all the player can see of it anyway;
+    /// * without a limit on passes, the number taken so far is left out.
+    pub fn canonical(&self) -> Canonical {
+        let mut rows: Vec<_> = self.row.iter().zip(self.visibility.iter()).map(|(stack, &hidden)| {
+            (if hidden > 0 { stack.first().cloned() } else { None }, hidden, stack[hidden..].to_vec())
+        }).collect();
+        if self.rules.stock == Stock::Runoff || self.deck.is_empty() {
+            rows.sort();
+        }
+        let mut goal = self.goal.clone();
+        goal.sort_by_key(|stack| stack.first().map(|x| x.suit));
+        Canonical {
+            deck: self.deck.clone(),
+            runoff: self.runoff.clone(),
+            rows,
+            goal,
+            redeals: if self.rules.passes.is_none() { 0 } else { self.redeals }
+        }
+    }
+    /// The rank foundation `k` takes next, if it isn't full. Jokers put away on it don't count.
+    /// Without `wrap`, a foundation started above the ace stops at the king.
+    fn next_rank(&self, k: usize) -> Option<Rank> {
+        match self.goal[k].iter().filter(|x| !x.is_joker()).count() {
+            13 => None,
+            len if self.base.index() + len >= 13 && !self.rules.wrap => None,
+            len => Some(RANKS[(self.base.index() + len) % 13])
+        }
+    }
+    /// Whether the card at `j` in row `i` can be picked up along with everything on top of it.
+    fn movable(&self, i: usize, j: usize) -> bool {
+        let stack = &self.row[i];
+        (j+1..stack.len()).all(|k| match self.rules.moves {
+            Moving::Any => true,
+            Moving::Built => self.rules.builds(&stack[k], &stack[k-1]),
+            Moving::InSuit => Building::SameSuit.allows(&stack[k], &stack[k-1]) && self.rules.builds(&stack[k], &stack[k-1])
+        })
+    }
+    /// Moves the cards from `idx` up in stack `from` onto stack `to`, keeping their order.
+    fn shift(&mut self, from: &StackId, idx: usize, to: &StackId) {
+        if from == to {
+            return;
+        }
+        let count = self.stack(from).map_or(0, |x| x.len().saturating_sub(idx));
+        for _ in 0..count {
+            let crd = self.get_mut_stack_for(from.clone()).unwrap().pop().unwrap();
+            self.get_mut_stack_for(to.clone()).unwrap().push(crd);
+        }
+        if let Some(stack) = self.get_mut_stack_for(to.clone()) {
+            let len = stack.len();
+            stack[len - count..].reverse();
         }
     }
     fn get_mut_stack_for(&mut self, id: StackId) -> Option<&mut Vec<Card>> {
@@ -73,29 +219,126 @@ impl Solitaire {
             _ => None
         }
     }
-}
-
-fn sol_card_to_ident(card: &Card) -> Ident {
-    Ident::new(card.suit*16 + card.rank)
+    /// Writes out the full state, one stack per line.
+    pub fn to_text(&self) -> Vec<String> {
+        let cards = |stack: &Vec<Card>| stack.iter().map(|x| format!(" {}", x.code())).collect::<String>();
+        let mut out = Vec::new();
+        out.push(format!("rules {}", self.rules.to_text()));
+        out.push(format!("redeals {}", self.redeals));
+        // only written when it matters, so klondike saves still load in older versions
+        if self.base != Rank::Ace {
+            out.push(format!("base {}", self.base.index()));
+        }
+        out.push(format!("deck{}", cards(&self.deck)));
+        out.push(format!("runoff{}", cards(&self.runoff)));
+        for (stack, hidden) in self.row.iter().zip(self.visibility.iter()) {
+            out.push(format!("row {}{}", hidden, cards(stack)));
+        }
+        for stack in &self.goal {
+            out.push(format!("goal{}", cards(stack)));
+        }
+        out
+    }
+    /// Reads back a state written by `to_text`.
+    pub fn from_text(lines: &[&str]) -> Result<Solitaire, String> {
+        let mut out = Solitaire {
+            deck: Vec::new(),
+            runoff: Vec::new(),
+            row: Vec::new(),
+            visibility: Vec::new(),
+            goal: Vec::new(),
+            base: Rank::Ace,
+            redeals: 0,
+            rules: Rules::default()
+        };
+        let mut seen = HashSet::new();
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let kind = words.next().unwrap_or("");
+            let number = |word: Option<&str>| word.and_then(|x| x.parse::<usize>().ok()).ok_or(format!("bad number in '{}'", line));
+            match kind {
+                "rules" => {
+                    out.rules = Rules::from_text(&line[5..])?;
+                    continue;
+                },
+                "redeals" => {
+                    out.redeals = number(words.next())?;
+                    continue;
+                },
+                "base" => {
+                    out.base = RANKS[number(words.next())? % 13];
+                    continue;
+                },
+                "row" => out.visibility.push(number(words.next())?),
+                "deck" | "runoff" | "goal" => (),
+                _ => return Err(format!("unexpected line '{}'", line))
+            }
+            let mut stack = Vec::new();
+            for word in words {
+                let card = Card::from_code(word).ok_or(format!("bad card '{}'", word))?;
+                if !seen.insert(card.clone()) {
+                    return Err(format!("{} appears twice", word));
+                }
+                stack.push(card);
+            }
+            match kind {
+                "deck" => out.deck = stack,
+                "runoff" => out.runoff = stack,
+                "row" => out.row.push(stack),
+                _ => out.goal.push(stack)
+            }
+        }
+        if out.row.is_empty() || out.goal.is_empty() {
+            return Err(String::from("missing rows or foundations"));
+        }
+        for (stack, hidden) in out.row.iter().zip(out.visibility.iter()) {
+            if *hidden > 0 && *hidden >= stack.len() {
+                return Err(String::from("a row has no face up cards"));
+            }
+        }
+        Ok(out)
+    }
+    /// Finds the stack holding `card` and its index within that stack.
+    pub fn locate(&self, card: &Card) -> Option<(StackId, usize)> {
+        let find = |stack: &Vec<Card>| stack.iter().position(|x| x == card);
+        if let Some(idx) = find(&self.deck) {
+            return Some((StackId(0,0), idx));
+        }
+        if let Some(idx) = find(&self.runoff) {
+            return Some((StackId(0,1), idx));
+        }
+        for (i, stack) in self.row.iter().enumerate() {
+            if let Some(idx) = find(stack) {
+                return Some((StackId(1,i), idx));
+            }
+        }
+        for (i, stack) in self.goal.iter().enumerate() {
+            if let Some(idx) = find(stack) {
+                return Some((StackId(2,i), idx));
+            }
+        }
+        None
+    }
 }
 
 impl Problem<CardGameAction, CardGamePercept> for Solitaire {
+    type Undo = SolitaireUndo;
     fn percept(&self) -> CardGamePercept {
         let mut map = HashMap::new();
         let mut deck = Vec::new();
         let mut runoff = Vec::new();
         for x in &self.deck {
-            deck.push((sol_card_to_ident(x),None));
+            deck.push((x.ident(),None));
         }
         map.insert(StackId(0,0),deck);
         for x in &self.runoff {
-            runoff.push((sol_card_to_ident(x),Some(x.clone())));
+            runoff.push((x.ident(),Some(x.clone())));
         }
         map.insert(StackId(0,1),runoff);
         for (i, stack) in self.row.iter().enumerate() {
             let mut thing = Vec::new();
             for (j, crd) in stack.iter().enumerate() {
-                thing.push((sol_card_to_ident(crd),if j < self.visibility[i] {
+                thing.push((crd.ident(),if j < self.visibility[i] {
                     None
                 } else {
                     Some(crd.clone())
@@ -106,18 +349,23 @@ impl Problem<CardGameAction, CardGamePercept> for Solitaire {
         for (i, stack) in self.goal.iter().enumerate() {
             let mut thing = Vec::new();
             for crd in stack {
-                thing.push((sol_card_to_ident(crd),Some(crd.clone())));
+                thing.push((crd.ident(),Some(crd.clone())));
             }
             map.insert(StackId(2,i),thing);
         }
         CardGamePercept {
-            stacks: map
+            stacks: map,
+            draw: self.rules.draw
         }
     }
     fn actions(&self) -> Vec<CardGameAction> {
         let mut out = Vec::new();
         let mut considerations = Vec::new();
-        if !self.deck.is_empty() || !self.runoff.is_empty() {
+        let refills = match self.rules.stock {
+            Stock::Runoff => !self.runoff.is_empty() && self.can_redeal(),
+            Stock::Rows => false
+        };
+        if !self.deck.is_empty() || refills {
             out.push(CardGameAction::Tap(StackId(0,0)));
         }
         if !self.runoff.is_empty() {
@@ -125,16 +373,25 @@ impl Problem<CardGameAction, CardGamePercept> for Solitaire {
         }
         for i in 0..self.row.len() {
             for j in self.visibility[i]..self.row[i].len() {
-                considerations.push(&self.row[i][j]);
+                if self.movable(i, j) {
+                    considerations.push(&self.row[i][j]);
+                }
+            }
+        }
+        // cards can be taken back down off the foundations
+        for stack in &self.goal {
+            if let Some(card) = stack.last() {
+                considerations.push(card);
             }
         }
         for current in considerations.drain(..) {
             for k in 0..self.row.len() {
                 if let Some(test) = self.row[k].last() {
-                    if test.color != current.color && test.rank == current.rank + 1 {
+                    // with cards out of sequence in a row, one can fit on top of its own row
+                    if self.rules.builds(current, test) && !self.row[k].contains(current) {
                         out.push(CardGameAction::Move(current.clone(), StackId(1,k)));
                     }
-                } else {
+                } else if self.rules.empty == Empty::AnyCard || current.is_joker() || current.rank == RANKS[(self.base.index() + 12) % 13] {
                     out.push(CardGameAction::Move(current.clone(), StackId(1,k)));
                 }
             }
@@ -149,7 +406,14 @@ impl Problem<CardGameAction, CardGamePercept> for Solitaire {
         }
         for current in considerations.drain(..) {
             for k in 0..self.goal.len() {
-                if current.rank == self.goal[k].len() && current.suit == k {
+                if current.is_joker() {
+                    if !self.goal[k].is_empty() {
+                        out.push(CardGameAction::Move(current.clone(), StackId(2,k)));
+                    }
+                    continue;
+                }
+                // an empty foundation belongs to whichever suit gets there first
+                if Some(current.rank) == self.next_rank(k) && self.goal[k].first().is_none_or(|x| x.suit == current.suit) {
                     out.push(CardGameAction::Move(current.clone(), StackId(2,k)));
                 }
             }
@@ -158,57 +422,86 @@ impl Problem<CardGameAction, CardGamePercept> for Solitaire {
     }
     fn result(&self, act: CardGameAction) -> Self {
         let mut out = self.clone();
+        out.apply(act);
+        out
+    }
+    fn apply(&mut self, act: CardGameAction) -> SolitaireUndo {
         match act {
             CardGameAction::Tap(StackId(0,0)) => {
-                if out.deck.is_empty() {
-                    mem::swap(&mut out.deck, &mut out.runoff);
-                    out.deck.reverse();
+                if self.rules.stock == Stock::Rows {
+                    let dealt = self.row.len().min(self.deck.len());
+                    for i in 0..dealt {
+                        let crd = self.deck.pop().unwrap();
+                        self.row[i].push(crd);
+                    }
+                    SolitaireUndo::Deal(dealt)
+                } else if self.deck.is_empty() {
+                    if !self.can_redeal() {
+                        return SolitaireUndo::Nothing;
+                    }
+                    mem::swap(&mut self.deck, &mut self.runoff);
+                    self.deck.reverse();
+                    self.redeals += 1;
+                    SolitaireUndo::Redeal
                 } else {
-                    for _ in 0..3 {
-                        if let Some(crd) = out.deck.pop() {
-                            out.runoff.push(crd);
-                        }
+                    let drawn = self.rules.draw.min(self.deck.len());
+                    for _ in 0..drawn {
+                        let crd = self.deck.pop().unwrap();
+                        self.runoff.push(crd);
                     }
+                    SolitaireUndo::Draw(drawn)
                 }
             },
             CardGameAction::Move(card, stack) => {
-                let mut tmp = Vec::new();
-                for i in 0..3 {
-                    let mut halt = false;
-                    let mut j = 0;
-                    while let Some(mut test) = out.get_mut_stack_for(StackId(i,j)) {
-                        for k in 0..test.len() {
-                            if test[k] == card {
-                                while test.len() > k {
-                                    tmp.push(test.pop().unwrap());
-                                }
-                                halt = true;
-                                break;
-                            }
-                        }
-                        if halt {
-                            break;
-                        }
-                        j=j+1;
-                    }
-                    if halt {
-                        break;
-                    }
+                let (from, idx) = match self.locate(&card) {
+                    Some(x) => x,
+                    None => return SolitaireUndo::Nothing
+                };
+                if self.get_mut_stack_for(stack.clone()).is_none() {
+                    return SolitaireUndo::Nothing;
                 }
-                if let Some(mut stack) = out.get_mut_stack_for(stack.clone()) {
-                    while let Some(val) = tmp.pop() {
-                        stack.push(val);
-                    }
+                let below = self.stack(&stack).unwrap().len();
+                self.shift(&from, idx, &stack);
+                // only the row the cards came from can be left with no face up card
+                let flipped = match from {
+                    StackId(1, i) if self.row[i].len() <= self.visibility[i] && !self.row[i].is_empty() => {
+                        self.visibility[i] -= 1;
+                        Some(i)
+                    },
+                    _ => None
+                };
+                SolitaireUndo::Move(from, stack, below, flipped)
+            },
+            _ => SolitaireUndo::Nothing
+        }
+    }
+    fn undo(&mut self, undo: SolitaireUndo) {
+        match undo {
+            SolitaireUndo::Draw(drawn) => {
+                for _ in 0..drawn {
+                    let crd = self.runoff.pop().unwrap();
+                    self.deck.push(crd);
                 }
-                for i in 0..out.row.len() {
-                    if out.row[i].len() <= out.visibility[i] && out.row[i].len() > 0 {
-                        out.visibility[i] = out.visibility[i] - 1;
-                    }
+            },
+            SolitaireUndo::Redeal => {
+                self.deck.reverse();
+                mem::swap(&mut self.deck, &mut self.runoff);
+                self.redeals -= 1;
+            },
+            SolitaireUndo::Deal(dealt) => {
+                for i in (0..dealt).rev() {
+                    let crd = self.row[i].pop().unwrap();
+                    self.deck.push(crd);
+                }
+            },
+            SolitaireUndo::Move(from, to, below, flipped) => {
+                if let Some(i) = flipped {
+                    self.visibility[i] += 1;
                 }
+                self.shift(&to, below, &from);
             },
-            _ => ()
+            SolitaireUndo::Nothing => ()
         }
-        out
     }
     fn is_goal(&self) -> bool {
         for v in &self.row {
@@ -220,9 +513,70 @@ impl Problem<CardGameAction, CardGamePercept> for Solitaire {
     }
 }
 
+impl Search<CardGameAction> for Solitaire {
+    type Key = Canonical;
+    fn key(&self) -> Canonical {
+        self.canonical()
+    }
+    fn priority(&self, act: &CardGameAction) -> i32 {
+        match *act {
+            // every empty foundation is as good as the first
+            CardGameAction::Move(_, StackId(2, k)) if self.goal[k].is_empty() && self.goal[..k].iter().any(|x| x.is_empty()) => -1,
+            // a joker can be put away whenever it's free, and one foundation is as good as another
+            CardGameAction::Move(ref card, StackId(2, k)) if card.is_joker() => {
+                if self.goal[..k].iter().any(|x| !x.is_empty()) { -1 } else { 5 }
+            },
+            CardGameAction::Move(ref card, StackId(2, _)) => 100 - ((card.rank.index() + 13 - self.base.index()) % 13) as i32,
+            CardGameAction::Move(ref card, StackId(1, k)) => {
+                match self.locate(card) {
+                    // moving a whole column into an empty one gains nothing
+                    Some((StackId(1, _), 0)) if self.row[k].is_empty() => -100,
+                    Some((StackId(1, i), idx)) if idx == self.visibility[i] => 50 + idx as i32,
+                    // splitting a built sequence is rarely useful, so try it after everything else
+                    Some((StackId(1, i), idx)) if self.rules.builds(card, &self.row[i][idx-1]) => -50,
+                    Some((StackId(1, _), _)) => 20,
+                    // only worth it to make room for something else, and never worth a hint
+                    Some((StackId(2, _), _)) => -40,
+                    _ => 30
+                }
+            },
+            _ => 0
+        }
+    }
+}
+
+/// A position with everything that can't change how it plays out left out. See
+/// `Solitaire::canonical`.
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub struct Canonical {
+    deck: Vec<Card>,
+    runoff: Vec<Card>,
+    /// Each row as its bottom face down card, how many face down cards there are, and the face
+    /// up cards on top of them.
+    rows: Vec<(Option<Card>, usize, Vec<Card>)>,
+    goal: Vec<Vec<Card>>,
+    redeals: usize
+}
+
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub struct StackId(pub usize, pub usize);
 
+/// What it takes to put a `Solitaire` back the way it was before an action.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SolitaireUndo {
+    /// This many cards were turned over from the deck onto the runoff.
+    Draw(usize),
+    /// The runoff was turned back over into the deck.
+    Redeal,
+    /// A card was dealt from the deck onto each of this many rows, from the left.
+    Deal(usize),
+    /// Cards were moved from `.0` onto `.1`, on top of the `.2` cards already there, turning
+    /// over the top card of row `.3` if there is one.
+    Move(StackId, StackId, usize, Option<usize>),
+    /// The action did nothing.
+    Nothing
+}
+
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum CardGameAction {
     Move(Card, StackId),
@@ -231,14 +585,16 @@ pub enum CardGameAction {
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CardGamePercept {
-    pub stacks: HashMap<StackId,Vec<(Ident,Option<Card>)>>
+    pub stacks: HashMap<StackId,Vec<(Ident,Option<Card>)>>,
+    /// How many runoff cards are fanned out.
+    pub draw: usize
 }
 
 fn get_stack_location(&StackId(i, j): &StackId) -> GridLocation {
     match i {
         0 => GridLocation::new(GridValue(j as i32*2, 0), GridValue(0, 0), 0),
         1 => GridLocation::new(GridValue(j as i32*2, 0), GridValue(2, 0), 0),
-        2 | _ => GridLocation::new(GridValue(j as i32*2+5, 0), GridValue(0, 0), 0),
+        _ => GridLocation::new(GridValue(j as i32*2+5, 0), GridValue(0, 0), 0),
     }
 }
 
@@ -259,7 +615,7 @@ impl Renderable for CardGamePercept {
     fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>> {
         if let Some(stack) = self.stacks.get(&id.0) {
             if let Some(idx) = id.1 {
-                if let Some(&(ref ident,ref val)) = stack.get(idx) {
+                if let Some((ident,val)) = stack.get(idx) {
                     let mut children = Vec::new();
                     let mut offset = GridLocation::new(GridValue(0,0), GridValue(0,0), idx as i32);
                     if (id.0).0 == 1 {
@@ -269,21 +625,21 @@ impl Renderable for CardGamePercept {
                             }
                         }
                         let mut count = 0;
-                        for i in 0..idx {
-                            if stack[i].1.is_none() {
-                                count = count +1;
+                        for x in &stack[..idx] {
+                            if x.1.is_none() {
+                                count += 1;
                             } else {
-                                count = count + 2;
+                                count += 2;
                             }
                         }
                         offset.y = GridValue(0, count);
                     }
-                    let draggable = children.len() > 0 || match id.0 {
-                        StackId(0,1) | StackId(1, _) => idx == stack.len() - 1,
+                    let draggable = !children.is_empty() || match id.0 {
+                        StackId(0,1) | StackId(1, _) | StackId(2, _) => idx == stack.len() - 1,
                         _ => false
                     };
                     if id.0 == StackId(0,1) {
-                        let mut start = stack.len() as i32 - 3;
+                        let mut start = stack.len() as i32 - self.draw as i32;
                         if start < 0 {
                             start = 0;
                         }
@@ -295,7 +651,7 @@ impl Renderable for CardGamePercept {
                         ident: ident.expand(),
                         pos: get_stack_location(&id.0) + offset,
                         display: match val {
-                            &Some(ref crd) => CardDisplay::Front(crd.clone()),
+                            Some(crd) => CardDisplay::Front(crd.clone()),
                             &None => CardDisplay::Back
                         },
                         drag_children: if draggable { Some(children) } else { None }
@@ -333,10 +689,136 @@ impl Renderable for CardGamePercept {
             }
         }
     }
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>> {
+        let top = |stack: &StackId| {
+            match self.stacks.get(stack) {
+                Some(cards) if !cards.is_empty() => Some((stack.clone(), Some(cards.len()-1))),
+                Some(_) => Some((stack.clone(), None)),
+                None => None
+            }
+        };
+        match act {
+            CardGameAction::Move(card, stack) => {
+                for (id, cards) in self.stacks.iter() {
+                    if let Some(idx) = cards.iter().position(|(_, x)| x.as_ref() == Some(card)) {
+                        return top(stack).map(|target| MouseAction::Drop((id.clone(), Some(idx)), target));
+                    }
+                }
+                None
+            },
+            CardGameAction::Tap(stack) => top(stack).map(MouseAction::Tap)
+        }
+    }
     fn get_grid_extents() -> (GridLocation, GridLocation) {
         (GridLocation::new(GridValue(-1,0), GridValue(-1,0), -1), GridLocation::new(GridValue(13,0), GridValue(3, 32), 20))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use game::problem::tests::walk;
+    use super::*;
+
+    /// Walks a run of deals, checking `apply` and `undo` against each other and the canonical
+    /// form along the way. Returns which kinds of undo came up.
+    fn walks(rules: Rules) -> HashSet<&'static str> {
+        let mut seen = HashSet::new();
+        for seed in 0..40 {
+            walk(Solitaire::deal_number(seed, rules.clone()), seed, 300, |undo| {
+                seen.insert(match *undo {
+                    SolitaireUndo::Draw(_) => "draw",
+                    SolitaireUndo::Redeal => "redeal",
+                    SolitaireUndo::Deal(_) => "deal",
+                    SolitaireUndo::Move(_, _, _, Some(_)) => "flip",
+                    SolitaireUndo::Move(..) => "move",
+                    SolitaireUndo::Nothing => "nothing"
+                });
+            });
+        }
+        seen
+    }
+
+    fn cards(codes: &str) -> Vec<Card> {
+        codes.split_whitespace().map(|x| x.parse().unwrap()).collect()
+    }
 
+    /// A game with all face up `rows`, the last of them empty, and `2C` on the runoff.
+    fn with_empty_row(rows: &[&str], rules: Rules) -> Solitaire {
+        let rows = rows.iter().map(|x| (0, cards(x))).chain(Some((0, Vec::new()))).collect();
+        Solitaire::from_stacks(Vec::new(), cards("2C"), rows, vec![Vec::new(); 4], Rank::Ace, 0, rules)
+    }
 
+    fn moves_to_empty(game: &Solitaire, code: &str) -> bool {
+        let empty = game.size().0 - 1;
+        game.actions().contains(&CardGameAction::Move(code.parse().unwrap(), StackId(1, empty)))
+    }
+
+    #[test]
+    fn jokers_dont_count_on_foundations() {
+        let mut hearts = cards("AH 2H");
+        hearts.insert(1, Card::joker(Color::Red, 0));
+        let goal = vec![hearts, cards("AS"), Vec::new(), Vec::new()];
+        let game = Solitaire::from_stacks(Vec::new(), Vec::new(), Vec::new(), goal, Rank::Ace, 0, Rules::default());
+        assert_eq!(game.foundation_count(), 3);
+    }
+
+    #[test]
+    fn foundations_wrap_only_when_asked() {
+        let goal = vec![cards("QH KH"), Vec::new(), Vec::new(), Vec::new()];
+        let onto_king = |wrap| {
+            let rules = Rules { wrap, foundation: Foundation::Dealt, ..Rules::default() };
+            let game = Solitaire::from_stacks(Vec::new(), cards("AH"), Vec::new(), goal.clone(), Rank::Queen, 0, rules);
+            game.actions().contains(&CardGameAction::Move("AH".parse().unwrap(), StackId(2, 0)))
+        };
+        assert!(onto_king(true));
+        assert!(!onto_king(false));
+    }
+
+    #[test]
+    fn empty_row_takes_kings() {
+        let game = with_empty_row(&["KS QH", "5D"], Rules::default());
+        assert_eq!(game.rules().empty, Empty::Kings);
+        assert!(moves_to_empty(&game, "KS"), "a king-led run can't move to an empty row");
+        for code in &["QH", "5D", "2C"] {
+            assert!(!moves_to_empty(&game, code), "{} can move to an empty row", code);
+        }
+        let game = with_empty_row(&["5D KH"], Rules::default());
+        assert!(moves_to_empty(&game, "KH"), "a lone king can't move to an empty row");
+    }
+
+    #[test]
+    fn empty_row_takes_anything() {
+        let game = with_empty_row(&["KS QH", "5D"], Rules { empty: Empty::AnyCard, ..Rules::default() });
+        for code in &["KS", "QH", "5D", "2C"] {
+            assert!(moves_to_empty(&game, code), "{} can't move to an empty row", code);
+        }
+    }
+
+    #[test]
+    fn undo_runoff() {
+        let seen = walks(Rules::default());
+        for kind in &["draw", "redeal", "move", "flip"] {
+            assert!(seen.contains(kind), "never saw {}", kind);
+        }
+        let seen = walks(Rules { draw: 1, passes: Some(2), jokers: 2, ..Rules::default() });
+        for kind in &["draw", "redeal", "flip"] {
+            assert!(seen.contains(kind), "never saw {}", kind);
+        }
+    }
+
+    #[test]
+    fn undo_rows() {
+        for name in &["agnes", "easthaven"] {
+            let seen = walks(Rules::named(name).unwrap());
+            for kind in &["deal", "move", "flip"] {
+                assert!(seen.contains(kind), "never saw {} in {}", kind, name);
+            }
+        }
+    }
+
+    #[test]
+    fn undo_yukon() {
+        let seen = walks(Rules::named("yukon").unwrap());
+        assert!(seen.contains("flip"));
+    }
+}
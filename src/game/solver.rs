@@ -0,0 +1,389 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use game::problem::Problem;
+
+/// The extra knowledge a search needs about a game on top of `Problem`.
+pub trait Search<A> {
+    type Key: Hash + Eq;
+    /// Positions with the same key are treated as the same node by the transposition table, so
+    /// the key should leave out anything that can't affect the outcome.
+    fn key(&self) -> Self::Key;
+    /// Actions with a higher priority are tried first.
+    fn priority(&self, act: &A) -> i32;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Outcome<A> {
+    /// The actions leading from the starting state to a goal.
+    Solved(Vec<A>),
+    /// Every reachable position was searched without finding a goal.
+    Unwinnable,
+    /// The node or time budget ran out first.
+    Unknown
+}
+
+#[derive(Debug, Clone)]
+pub struct Report<A> {
+    pub outcome: Outcome<A>,
+    pub nodes: usize
+}
+
+/// The legal actions in `state`, ordered so that the most promising one is last.
+pub fn ordered_actions<S, A, P>(state: &S) -> Vec<A> where S: Problem<A, P> + Search<A> {
+    let mut acts: Vec<(i32, A)> = state.actions().into_iter().map(|act| (state.priority(&act), act)).collect();
+    acts.reverse();
+    acts.sort_by_key(|&(priority, _)| priority);
+    acts.into_iter().map(|(_, act)| act).collect()
+}
+
+/// What a depth-first search is told about each position it hasn't been to before.
+enum Step {
+    /// It has been searched already.
+    Skip,
+    /// It needs searching.
+    Go,
+    /// It is a goal.
+    Goal,
+    /// The search has to stop here.
+    Stop
+}
+
+/// Searches depth first from `state` until `visit` reports a goal, leaving the way there in
+/// `path`, or until there is nowhere left to go or `visit` says to stop. Returns whether a goal
+/// was found. `state` is played forwards and back, and is left wherever the search ended.
+fn depth_first<S, A, P, F>(state: &mut S, path: &mut Vec<A>, mut visit: F) -> bool where S: Problem<A, P> + Search<A>, A: Clone, F: FnMut(&S) -> Step {
+    let mut stack = Vec::new();
+    stack.push((ordered_actions(state), None));
+    while !stack.is_empty() {
+        let next = stack.last_mut().unwrap().0.pop();
+        if let Some(act) = next {
+            let undo = state.apply(act.clone());
+            match visit(state) {
+                Step::Skip => {
+                    state.undo(undo);
+                    continue;
+                },
+                Step::Go => (),
+                Step::Goal => {
+                    path.push(act);
+                    return true;
+                },
+                Step::Stop => return false
+            }
+            path.push(act);
+            let acts = ordered_actions(state);
+            stack.push((acts, Some(undo)));
+        } else {
+            if let Some(undo) = stack.pop().unwrap().1 {
+                state.undo(undo);
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+/// Takes the detours out of a solution. The search can wander a long way before it gets
+/// anywhere, through positions that are the same as far as their keys go, so this finds the
+/// fewest actions from `start` to the goal that go by way of positions with keys the solution
+/// already passes through, dropping everything between two with the same key.
+///
+/// A position with the same key as one on the way may need different actions to play on from,
+/// such as when its rows are in another order, so the actions are found afresh from the
+/// positions actually reached. If that somehow fails, `path` is given back as it was.
+pub fn shorten<S, A, P>(start: &S, path: Vec<A>) -> Vec<A> where S: Problem<A, P> + Search<A> + Clone, A: Clone {
+    // where each key is last seen along the way
+    let mut index = HashMap::new();
+    let mut state = start.clone();
+    index.insert(state.key(), 0);
+    for (i, act) in path.iter().enumerate() {
+        state.apply(act.clone());
+        index.insert(state.key(), i + 1);
+    }
+    let goal = path.len();
+    // breadth first, so the first way found to the goal is the shortest
+    let mut reached: Vec<Option<S>> = vec![None; goal + 1];
+    let mut prev = vec![None; goal + 1];
+    let first = index[&start.key()];
+    reached[first] = Some(start.clone());
+    let mut queue = VecDeque::new();
+    queue.push_back(first);
+    while let Some(i) = queue.pop_front() {
+        if i == goal {
+            break;
+        }
+        let state = reached[i].clone().unwrap();
+        for act in state.actions() {
+            let next = state.result(act.clone());
+            if let Some(&j) = index.get(&next.key()) {
+                if reached[j].is_none() {
+                    reached[j] = Some(next);
+                    prev[j] = Some((i, act));
+                    queue.push_back(j);
+                }
+            }
+        }
+    }
+    if !reached[goal].as_ref().is_some_and(|x| x.is_goal()) {
+        return path;
+    }
+    let mut out = Vec::new();
+    let mut at = goal;
+    while let Some((i, act)) = prev[at].take() {
+        out.push(act);
+        at = i;
+    }
+    out.reverse();
+    out
+}
+
+/// Depth-first search for a sequence of actions that reaches a goal, giving up after visiting
+/// `budget` distinct positions. A single copy of the game is played forwards and back, so only
+/// the keys of the positions seen are kept. The solution found is shortened before it is
+/// returned.
+pub fn solve<S, A, P>(start: &S, budget: usize) -> Report<A> where S: Problem<A, P> + Search<A> + Clone, A: Clone {
+    solve_within(start, budget, None)
+}
+
+/// The same search as `solve`, also giving up once it has taken longer than `time`.
+pub fn solve_within<S, A, P>(start: &S, budget: usize, time: Option<Duration>) -> Report<A> where S: Problem<A, P> + Search<A> + Clone, A: Clone {
+    let started = Instant::now();
+    let mut seen = HashSet::new();
+    let mut nodes = 1;
+    let mut gave_up = false;
+    seen.insert(start.key());
+    if start.is_goal() {
+        return Report { outcome: Outcome::Solved(Vec::new()), nodes };
+    }
+    let mut state = start.clone();
+    let mut path = Vec::new();
+    let found = depth_first(&mut state, &mut path, |state| {
+        if !seen.insert(state.key()) {
+            return Step::Skip;
+        }
+        nodes += 1;
+        if state.is_goal() {
+            return Step::Goal;
+        }
+        // the clock is only checked now and then, it costs more than a node
+        let late = nodes % 1024 == 0 && time.is_some_and(|time| started.elapsed() >= time);
+        if nodes >= budget || late {
+            gave_up = true;
+            return Step::Stop;
+        }
+        Step::Go
+    });
+    Report {
+        outcome: if found { Outcome::Solved(shorten(start, path)) } else if gave_up { Outcome::Unknown } else { Outcome::Unwinnable },
+        nodes
+    }
+}
+
+/// A set of keys that many threads can add to at once. It is split into shards with a lock
+/// each, so threads seldom wait on one another.
+struct Table<K> {
+    shards: Vec<Mutex<HashSet<K>>>
+}
+
+impl<K: Hash + Eq> Table<K> {
+    fn new(shards: usize) -> Table<K> {
+        Table {
+            shards: (0..shards).map(|_| Mutex::new(HashSet::new())).collect()
+        }
+    }
+    /// Adds `key`, returning whether it was new.
+    fn insert(&self, key: K) -> bool {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let shard = hasher.finish() as usize % self.shards.len();
+        self.shards[shard].lock().unwrap().insert(key)
+    }
+}
+
+/// The same search as `solve_within`, spread over `threads` threads.
+///
+/// The positions a few moves in are handed out to the threads, most promising first, and every
+/// position any of them reaches goes in one shared table, so none is searched twice. With one
+/// thread this is just `solve_within`; with more, which solution turns up first and how many
+/// positions it takes depend on how the threads happen to run.
+pub fn solve_parallel<S, A, P>(start: &S, budget: usize, time: Option<Duration>, threads: usize) -> Report<A>
+    where S: Problem<A, P> + Search<A> + Clone + Send + 'static, A: Clone + Send + 'static, S::Key: Send + 'static, P: 'static {
+    if threads <= 1 {
+        return solve_within(start, budget, time);
+    }
+    let started = Instant::now();
+    let seen = Arc::new(Table::new(threads * 16));
+    let mut nodes = 1;
+    seen.insert(start.key());
+    if start.is_goal() {
+        return Report { outcome: Outcome::Solved(Vec::new()), nodes };
+    }
+    // enough work to go round, found breadth first
+    let mut work = VecDeque::new();
+    work.push_back((start.clone(), Vec::new()));
+    while work.len() < threads * 4 {
+        let (state, path) = match work.pop_front() {
+            Some(x) => x,
+            None => return Report { outcome: Outcome::Unwinnable, nodes }
+        };
+        for act in ordered_actions(&state).into_iter().rev() {
+            let next = state.result(act.clone());
+            if !seen.insert(next.key()) {
+                continue;
+            }
+            nodes += 1;
+            let mut path = path.clone();
+            path.push(act);
+            if next.is_goal() {
+                return Report { outcome: Outcome::Solved(shorten(start, path)), nodes };
+            }
+            // there are only a few of these, so the clock can be checked every time
+            if nodes >= budget || time.is_some_and(|time| started.elapsed() >= time) {
+                return Report { outcome: Outcome::Unknown, nodes };
+            }
+            work.push_back((next, path));
+        }
+    }
+    let work = Arc::new(Mutex::new(work));
+    let count = Arc::new(AtomicUsize::new(nodes));
+    let stop = Arc::new(AtomicBool::new(false));
+    let gave_up = Arc::new(AtomicBool::new(false));
+    let solution = Arc::new(Mutex::new(None));
+    let workers: Vec<_> = (0..threads).map(|_| {
+        let (work, seen, count, stop, gave_up, solution) = (work.clone(), seen.clone(), count.clone(), stop.clone(), gave_up.clone(), solution.clone());
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let next = work.lock().unwrap().pop_front();
+                let (mut state, mut path) = match next {
+                    Some(x) => x,
+                    None => return
+                };
+                let found = depth_first(&mut state, &mut path, |state: &S| {
+                    if stop.load(Ordering::Relaxed) {
+                        return Step::Stop;
+                    }
+                    if !seen.insert(state.key()) {
+                        return Step::Skip;
+                    }
+                    let nodes = count.fetch_add(1, Ordering::Relaxed) + 1;
+                    if state.is_goal() {
+                        return Step::Goal;
+                    }
+                    let late = nodes % 1024 == 0 && time.is_some_and(|time| started.elapsed() >= time);
+                    if nodes >= budget || late {
+                        gave_up.store(true, Ordering::Relaxed);
+                        return Step::Stop;
+                    }
+                    Step::Go
+                });
+                if found {
+                    let mut solution = solution.lock().unwrap();
+                    if solution.is_none() {
+                        *solution = Some(path);
+                    }
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let nodes = count.load(Ordering::Relaxed);
+    let outcome = match solution.lock().unwrap().take() {
+        Some(path) => Outcome::Solved(shorten(start, path)),
+        None if gave_up.load(Ordering::Relaxed) => Outcome::Unknown,
+        None => Outcome::Unwinnable
+    };
+    Report { outcome, nodes }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use game::freecell::FreeCell;
+    use game::rules::Rules;
+    use game::solitaire::{Solitaire, CardGameAction, StackId};
+    use super::*;
+
+    /// Plays `path` from `start`, checking that every action in it is legal, and gives back where
+    /// it ends up.
+    fn replay<S, A, P>(start: &S, path: &[A]) -> S where S: Problem<A, P> + Clone, A: Clone + PartialEq + Debug {
+        let mut state = start.clone();
+        for act in path {
+            assert!(state.actions().contains(act), "{:?} can't be played", act);
+            state.apply(act.clone());
+        }
+        state
+    }
+
+    fn solution<A: Debug>(report: Report<A>) -> Vec<A> {
+        match report.outcome {
+            Outcome::Solved(path) => path,
+            outcome => panic!("expected a solution, got {:?}", outcome)
+        }
+    }
+
+    #[test]
+    fn solves_winnable_deals() {
+        let start = Solitaire::deal_number(4, Rules::default());
+        let path = solution(solve(&start, 100000));
+        assert!(replay(&start, &path).is_goal());
+        let start = FreeCell::deal_number(3);
+        let path = solution(solve(&start, 100000));
+        assert!(replay(&start, &path).is_goal());
+    }
+
+    #[test]
+    fn proves_unwinnable_deals() {
+        let report = solve(&Solitaire::deal_number(1, Rules::default()), 100000);
+        assert_eq!(report.outcome, Outcome::Unwinnable);
+        assert!(report.nodes < 100000);
+    }
+
+    #[test]
+    fn gives_up_at_budget() {
+        let report = solve(&Solitaire::deal_number(2, Rules::default()), 1000);
+        assert_eq!(report.outcome, Outcome::Unknown);
+        assert_eq!(report.nodes, 1000);
+    }
+
+    #[test]
+    fn shortens_detours() {
+        let start = Solitaire::deal_number(4, Rules::default());
+        let path = solution(solve(&start, 100000));
+        // going through the whole deck and turning it back over gets nowhere
+        let mut detour = vec![CardGameAction::Tap(StackId(0,0)); 9];
+        detour.extend(path.iter().cloned());
+        assert!(replay(&start, &detour).is_goal());
+        let short = shorten(&start, detour);
+        assert!(short.len() <= path.len());
+        assert!(replay(&start, &short).is_goal());
+    }
+
+    #[test]
+    fn gives_up_on_time() {
+        let start = Solitaire::deal_number(2, Rules::default());
+        let report = solve_within(&start, 100000, Some(Duration::new(0, 0)));
+        assert_eq!(report.outcome, Outcome::Unknown);
+        assert!(report.nodes < 100000);
+        // before the threads are even handed any work
+        let report = solve_parallel(&start, 100000, Some(Duration::new(0, 0)), 4);
+        assert_eq!(report.outcome, Outcome::Unknown);
+        assert!(report.nodes < 16);
+    }
+
+    #[test]
+    fn solves_in_parallel() {
+        let start = Solitaire::deal_number(4, Rules::default());
+        let path = solution(solve_parallel(&start, 100000, None, 2));
+        assert!(replay(&start, &path).is_goal());
+        let report = solve_parallel(&Solitaire::deal_number(1, Rules::default()), 100000, None, 2);
+        assert_eq!(report.outcome, Outcome::Unwinnable);
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use game::cards::*;
+use game::problem::Problem;
+use game::render::*;
+use game::grid::*;
+use game::solitaire::StackId;
+use game::solver::Search;
+use game::deal;
+
+/// A game of Spider, played with two decks. The stock is `StackId(0,0)`, the columns
+/// `StackId(1,_)` and each completed king to ace run is laid aside on its own `StackId(2,_)`.
+///
+/// With fewer than four suits the same card turns up several times; the copies are told apart
+/// by their `deck`.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Spider {
+    stock: Vec<Card>,
+    columns: Vec<Vec<Card>>,
+    hidden: Vec<usize>,
+    goal: Vec<Vec<Card>>
+}
+
+/// Moves the cards in column `.0` from index `.1` up onto column `.2`, or deals a card from the
+/// stock onto every column.
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub enum SpiderAction {
+    Move(usize, usize, usize),
+    Deal
+}
+
+impl Spider {
+    /// Deals the first `dealt` cards out across the columns one at a time, left to right, with
+    /// only the top card of each face up. The rest make up the stock.
+    pub fn deal(cards: Vec<Card>, columns: usize, dealt: usize) -> Self {
+        let mut out = Spider {
+            stock: Vec::new(),
+            columns: vec![Vec::new(); columns],
+            hidden: vec![0; columns],
+            goal: Vec::new()
+        };
+        let mut cards = cards.into_iter();
+        for i in 0..dealt {
+            if let Some(card) = cards.next() {
+                out.columns[i % columns].push(card);
+            }
+        }
+        out.stock = cards.collect();
+        out.stock.reverse();
+        for i in 0..columns {
+            out.hidden[i] = out.columns[i].len().saturating_sub(1);
+        }
+        out
+    }
+    /// Deals the standard game for a deal number, using two decks' worth of cards in `suits`
+    /// suits: one suit is all spades, two is spades and hearts.
+    pub fn deal_number(seed: u64, suits: usize) -> Self {
+        let cards = deal::Deck {
+            suits: SUITS[..suits].to_vec(),
+            decks: 8 / suits,
+            ..deal::Deck::standard()
+        }.shuffled(seed);
+        Spider::deal(cards, 10, 54)
+    }
+    /// Moves the cards in column `i` from `idx` up onto column `k`, keeping their order.
+    fn shift(&mut self, i: usize, idx: usize, k: usize) {
+        let count = self.columns[i].len() - idx;
+        for _ in 0..count {
+            let card = self.columns[i].pop().unwrap();
+            self.columns[k].push(card);
+        }
+        let len = self.columns[k].len();
+        self.columns[k][len - count..].reverse();
+    }
+    /// The number of runs that have been completed.
+    pub fn completed(&self) -> usize {
+        self.goal.len()
+    }
+    /// The index where the same-suit run at the top of column `i` starts.
+    fn run_start(&self, i: usize) -> usize {
+        let stack = &self.columns[i];
+        let mut start = stack.len().saturating_sub(1);
+        while start > self.hidden[i] && follows(&stack[start], &stack[start-1]) {
+            start -= 1;
+        }
+        start
+    }
+    /// Lays aside a completed run from the top of any column, and turns over the cards left
+    /// uncovered, noting both in `undo`.
+    fn tidy(&mut self, undo: &mut SpiderUndo) {
+        for i in 0..self.columns.len() {
+            let len = self.columns[i].len();
+            if len >= 13 && len - self.run_start(i) >= 13 && self.columns[i][len-13].rank == Rank::King {
+                let run = self.columns[i].split_off(len-13);
+                self.goal.push(run);
+                undo.laid.push(i);
+            }
+            if self.hidden[i] > 0 && self.hidden[i] >= self.columns[i].len() {
+                undo.flipped.push((i, self.hidden[i]));
+                self.hidden[i] = self.columns[i].len() - 1;
+            }
+        }
+    }
+}
+
+/// What it takes to put a `Spider` back the way it was before an action.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SpiderUndo {
+    /// For a move, how many cards were on the column moved onto; for a deal, how many columns
+    /// were dealt a card, from the left.
+    below: usize,
+    act: SpiderAction,
+    /// The columns a completed run was laid aside from, in order.
+    laid: Vec<usize>,
+    /// The columns that had cards turned over, with how many were face down before.
+    flipped: Vec<(usize, usize)>
+}
+
+fn follows(card: &Card, under: &Card) -> bool {
+    card.suit == under.suit && card.rank.next() == Some(under.rank)
+}
+
+impl Problem<SpiderAction, SpiderPercept> for Spider {
+    type Undo = SpiderUndo;
+    fn percept(&self) -> SpiderPercept {
+        let cards = |stack: &Vec<Card>, hidden: usize| {
+            stack.iter().enumerate().map(|(j, card)| {
+                (card.ident(), if j < hidden { None } else { Some(card.clone()) })
+            }).collect()
+        };
+        let mut map = HashMap::new();
+        map.insert(StackId(0,0), cards(&self.stock, self.stock.len()));
+        for (i, stack) in self.columns.iter().enumerate() {
+            map.insert(StackId(1,i), cards(stack, self.hidden[i]));
+        }
+        for i in 0..8 {
+            map.insert(StackId(2,i), self.goal.get(i).map_or(Vec::new(), |x| cards(x, 0)));
+        }
+        SpiderPercept {
+            stacks: map
+        }
+    }
+    fn actions(&self) -> Vec<SpiderAction> {
+        let mut out = Vec::new();
+        for i in 0..self.columns.len() {
+            let stack = &self.columns[i];
+            for (idx, card) in stack.iter().enumerate().skip(self.run_start(i)) {
+                for k in 0..self.columns.len() {
+                    if k != i && self.columns[k].last().is_none_or(|under| card.rank.next() == Some(under.rank)) {
+                        out.push(SpiderAction::Move(i, idx, k));
+                    }
+                }
+            }
+        }
+        // the stock can't be dealt while a column is empty
+        if !self.stock.is_empty() && self.columns.iter().all(|x| !x.is_empty()) {
+            out.push(SpiderAction::Deal);
+        }
+        out
+    }
+    fn result(&self, act: SpiderAction) -> Self {
+        let mut out = self.clone();
+        out.apply(act);
+        out
+    }
+    fn apply(&mut self, act: SpiderAction) -> SpiderUndo {
+        let mut undo = SpiderUndo {
+            below: 0,
+            act: act.clone(),
+            laid: Vec::new(),
+            flipped: Vec::new()
+        };
+        match act {
+            SpiderAction::Move(i, idx, k) => {
+                if i < self.columns.len() && k < self.columns.len() && i != k && idx <= self.columns[i].len() {
+                    undo.below = self.columns[k].len();
+                    self.shift(i, idx, k);
+                } else {
+                    // does nothing, just like dealing no cards
+                    undo.act = SpiderAction::Deal;
+                }
+            },
+            SpiderAction::Deal => {
+                undo.below = self.columns.len().min(self.stock.len());
+                for i in 0..undo.below {
+                    let card = self.stock.pop().unwrap();
+                    self.columns[i].push(card);
+                }
+            }
+        }
+        self.tidy(&mut undo);
+        undo
+    }
+    fn undo(&mut self, undo: SpiderUndo) {
+        for (i, hidden) in undo.flipped {
+            self.hidden[i] = hidden;
+        }
+        for &i in undo.laid.iter().rev() {
+            let mut run = self.goal.pop().unwrap();
+            self.columns[i].append(&mut run);
+        }
+        match undo.act {
+            SpiderAction::Move(i, _, k) => self.shift(k, undo.below, i),
+            SpiderAction::Deal => {
+                for i in (0..undo.below).rev() {
+                    let card = self.columns[i].pop().unwrap();
+                    self.stock.push(card);
+                }
+            }
+        }
+    }
+    fn is_goal(&self) -> bool {
+        self.stock.is_empty() && self.columns.iter().all(|x| x.is_empty())
+    }
+}
+
+impl Search<SpiderAction> for Spider {
+    /// The stock is only ever dealt from the top, so its size says all there is to say about
+    /// it. Which copy of a card is which, and which column is which, make no difference.
+    type Key = (usize, Vec<(usize, Vec<(Suit, Rank)>)>);
+    fn key(&self) -> Self::Key {
+        let mut columns: Vec<(usize, Vec<(Suit, Rank)>)> = self.columns.iter().zip(self.hidden.iter())
+            .map(|(stack, &hidden)| (hidden, stack.iter().map(|card| (card.suit, card.rank)).collect()))
+            .collect();
+        columns.sort();
+        (self.stock.len(), columns)
+    }
+    fn priority(&self, act: &SpiderAction) -> i32 {
+        match *act {
+            SpiderAction::Move(i, idx, k) => {
+                let card = &self.columns[i][idx];
+                let uncovers = idx > 0 && idx == self.hidden[i];
+                match self.columns[k].last() {
+                    None if idx == 0 => -100,
+                    None => if uncovers { 10 } else { -20 },
+                    // splitting a run only shuffles it between columns
+                    Some(_) if idx > 0 && idx > self.hidden[i] && follows(card, &self.columns[i][idx-1]) => -50,
+                    Some(under) => {
+                        match (under.suit == card.suit, uncovers) {
+                            (true, true) => 80,
+                            (true, false) => 50,
+                            (false, true) => 40,
+                            // emptying a column only helps if something better goes in it
+                            (false, false) if idx == 0 => 0,
+                            // from one card of the wrong suit to another
+                            (false, false) => -30
+                        }
+                    }
+                }
+            },
+            SpiderAction::Deal => -10
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SpiderPercept {
+    pub stacks: HashMap<StackId,Vec<(Ident,Option<Card>)>>
+}
+
+fn get_stack_location(&StackId(i, j): &StackId) -> GridLocation {
+    match i {
+        0 => GridLocation::new(GridValue(0, 0), GridValue(0, 0), 0),
+        1 => GridLocation::new(GridValue(j as i32*2, 0), GridValue(2, 0), 0),
+        _ => GridLocation::new(GridValue(j as i32*2+4, 0), GridValue(0, 0), 0),
+    }
+}
+
+impl Renderable for SpiderPercept {
+    type CardId = (StackId, Option<usize>);
+    type Action = SpiderAction;
+    fn get_cards(&self) -> Vec<Self::CardId> {
+        let mut out = Vec::new();
+        for (k, val) in self.stacks.iter() {
+            out.push((k.clone(), None));
+            for i in 0..val.len() {
+                out.push((k.clone(), Some(i)));
+            }
+        }
+        out
+    }
+    fn get_data_for(&self, id: Self::CardId) -> Option<CardData<Self::CardId>> {
+        let stack = self.stacks.get(&id.0)?;
+        let idx = match id.1 {
+            Some(idx) => idx,
+            None => return Some(CardData {
+                ident: Ident::new((id.0).0*16 + (id.0).1).interleave(),
+                pos: get_stack_location(&id.0) - GridLocation::new(GridValue(0,0), GridValue(0,0), 1),
+                display: CardDisplay::Empty,
+                drag_children: None
+            })
+        };
+        let (ident, val) = stack.get(idx)?;
+        let mut offset = GridLocation::new(GridValue(0,0), GridValue(0,0), idx as i32);
+        let mut drag_children = None;
+        if (id.0).0 == 1 {
+            let mut count = 0;
+            for x in &stack[..idx] {
+                count += if x.1.is_none() { 1 } else { 2 };
+            }
+            offset.y = GridValue(0, count);
+            let ordered = val.is_some() && (idx+1..stack.len()).all(|i| match (&stack[i].1, &stack[i-1].1) {
+                (Some(card), Some(under)) => follows(card, under),
+                _ => false
+            });
+            if ordered {
+                drag_children = Some((idx+1..stack.len()).map(|i| (id.0.clone(), Some(i))).collect());
+            }
+        }
+        Some(CardData {
+            ident: ident.expand(),
+            pos: get_stack_location(&id.0) + offset,
+            display: match val {
+                Some(crd) => CardDisplay::Front(crd.clone()),
+                &None => CardDisplay::Back
+            },
+            drag_children
+        })
+    }
+    fn get_action_for(&self, act: MouseAction<Self::CardId>) -> Option<Self::Action> {
+        match act {
+            MouseAction::Drop((StackId(1, i), Some(idx)), (StackId(1, k), _)) => Some(SpiderAction::Move(i, idx, k)),
+            MouseAction::Tap((StackId(0, 0), _)) => Some(SpiderAction::Deal),
+            _ => None
+        }
+    }
+    fn get_mouse_action_for(&self, act: &Self::Action) -> Option<MouseAction<Self::CardId>> {
+        let top = |stack: StackId| {
+            self.stacks.get(&stack).map(|cards| (stack.clone(), cards.len().checked_sub(1)))
+        };
+        match *act {
+            SpiderAction::Move(i, idx, k) => top(StackId(1,k)).map(|target| MouseAction::Drop((StackId(1,i), Some(idx)), target)),
+            SpiderAction::Deal => top(StackId(0,0)).map(MouseAction::Tap)
+        }
+    }
+    fn get_grid_extents() -> (GridLocation, GridLocation) {
+        (GridLocation::new(GridValue(-1,0), GridValue(-1,0), -1), GridLocation::new(GridValue(19,0), GridValue(3, 60), 20))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::problem::tests::walk;
+    use super::*;
+
+    #[test]
+    fn undo() {
+        let mut laid = false;
+        for seed in 0..40 {
+            for &suits in &[1, 2, 4] {
+                walk(Spider::deal_number(seed, suits), seed, 400, |undo| laid = laid || !undo.laid.is_empty());
+            }
+        }
+        assert!(laid, "never laid a run aside");
+    }
+}
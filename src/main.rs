@@ -6,6 +6,7 @@ mod cmdline;
 mod game;
 mod raytrace;
 mod springy;
+mod status;
 
 use amethyst::{
     Application,
@@ -44,7 +45,10 @@ use amethyst::ecs::{
     World,
 };
 
+use game::cards::{Suit, Rank, Color};
+use cmdline::Table;
 use game::problem::Problem;
+use game::solver::Search;
 use game::render::{
     MouseAction,
     CardDisplay,
@@ -140,18 +144,24 @@ impl System<()> for CardSystem {
 fn get_card_asset_id(card: CardDisplay) -> String
 {
     if let CardDisplay::Front(card) = card {
+        if card.is_joker() {
+            return format!("cards/card_joker_{}", match card.color() {
+                Color::Black => "black",
+                Color::Red => "red"
+            });
+        }
         let suit = match card.suit {
-            0 => "spades",
-            1 => "hearts",
-            2 => "clubs",
-            3 | _ => "diamonds"
+            Suit::Spades => "spades",
+            Suit::Hearts => "hearts",
+            Suit::Clubs => "clubs",
+            Suit::Diamonds => "diamonds"
         };
         let rank = match card.rank {
-            0 => "ace".to_string(),
-            10 => "jack".to_string(),
-            11 => "queen".to_string(),
-            12 => "king".to_string(),
-            x => format!("{}", x+1)
+            Rank::Ace => "ace".to_string(),
+            Rank::Jack => "jack".to_string(),
+            Rank::Queen => "queen".to_string(),
+            Rank::King => "king".to_string(),
+            x => format!("{}", x.value())
         };
         format!("cards/card_{}_{}", rank, suit)
     } else if CardDisplay::Back == card {
@@ -161,23 +171,33 @@ fn get_card_asset_id(card: CardDisplay) -> String
     }
 }
 
-struct Test {
-    state: game::solitaire::Solitaire,
+/// How many positions the hint search may visit looking for a winning line.
+const HINT_BUDGET: usize = 20000;
+
+struct Test<S: Table> {
+    opts: cmdline::Options,
+    session: cmdline::Session<S>,
     drag: Option<<game::solitaire::CardGamePercept as SRenderable>::CardId>,
     spacing: [f32; 3],
     mouse: (f32, f32),
-    mouseray: raytrace::Ray
+    mouseray: raytrace::Ray,
+    paused: bool,
+    wait: f32,
+    hints: Vec<S::Action>,
+    hint: usize
 }
 
 fn ren_to_world(pos: &GridLocation) -> [f32; 3] {
     [pos.x.to_float(1.25, 0.25), pos.y.to_float(-1.875, -0.25), pos.sort as f32*0.001]
 }
 
-impl Test {
+impl <S> Test<S> where S: Table + Problem<<S as Table>::Action, <S as Table>::Percept> + Search<<S as Table>::Action> {
     fn refresh(&mut self, asset_manager: &mut AssetManager, world: &mut World) {
+        self.hints.clear();
+        self.print_status();
         let mut map = HashMap::new();
-        let percept = self.state.percept();
-        for card in self.state.percept().get_cards() {
+        let percept = self.session.history.current().percept();
+        for card in self.session.history.current().percept().get_cards() {
             if let Some(data) = percept.get_data_for(card.clone()) {
                 map.insert(data.ident, card);
             }
@@ -189,11 +209,11 @@ impl Test {
         }
 
     }
-    fn do_thing(&mut self, act: game::solitaire::CardGameAction, asset_manager: &mut AssetManager, world: &mut World) {
-        println!("{:?}", act);
-        let actions = self.state.actions();
+    fn do_thing(&mut self, act: S::Action, asset_manager: &mut AssetManager, world: &mut World) {
+        println!("{}", S::format_move(&act));
+        let actions = self.session.history.current().actions();
         if actions.contains(&act) {
-            self.state = self.state.result(act);
+            self.session.history.apply(act);
             self.refresh(asset_manager, world);
         }
     }
@@ -218,7 +238,7 @@ impl Test {
         match evt {
             Event::MouseMoved(x, y) => {
                 if let Some(ref card) = self.drag {
-                    let percept = self.state.percept();
+                    let percept = self.session.history.current().percept();
                     let data = percept.get_data_for(card.clone()).unwrap();
                     if let Some(ref children) = data.drag_children {
                         let base = ren_to_world(&data.pos);
@@ -239,7 +259,7 @@ impl Test {
                 let mut ignore = Vec::new();
                 if let Some(card) = self.drag.clone() {
                     ignore.push(card.clone());
-                    if let Some(mut children) = self.state.percept().get_data_for(card).unwrap().drag_children {
+                    if let Some(mut children) = self.session.history.current().percept().get_data_for(card).unwrap().drag_children {
                         for child in children.drain(..) {
                             ignore.push(child);
                         }
@@ -267,8 +287,9 @@ impl Test {
                 }
                 println!("{:?}", target);
                 if let Some((_,target)) = target {
-                    let percept = self.state.percept();
+                    let percept = self.session.history.current().percept();
                     if state == amethyst::ElementState::Pressed {
+                        self.session.queue.clear();
                         if percept.get_data_for(target.clone()).unwrap().drag_children.is_some() {
                             self.drag = Some(target);
                         }
@@ -291,24 +312,81 @@ impl Test {
     }
     fn mush(&mut self) {
         let mut done = false;
+        self.session.history.begin();
         while !done {
-            let acts = self.state.actions();
+            let acts = self.session.history.current().actions();
             done = true;
             for a in acts {
-                match a {
-                    game::solitaire::CardGameAction::Move(_, game::solitaire::StackId(2, _)) => {
-                        self.state = self.state.result(a);
-                        done = false;
-                        break;
-                    },
-                    _ => ()
+                if S::plays_home(&a) {
+                    self.session.history.apply(a);
+                    done = false;
+                    break;
                 }
             }
         }
+        self.session.history.commit();
+    }
+    fn print_status(&self) {
+        println!("{}", self.session.status());
+    }
+    /// Writes the status out in the glyphs above the layout. It is redrawn every frame, so the
+    /// timer keeps running and every move, undo and redeal shows straight away.
+    fn draw_status(&self, world: &mut World, asset_manager: &mut AssetManager) {
+        let text: Vec<char> = self.session.status().chars().collect();
+        let (low, high) = <S::Percept as SRenderable>::get_grid_extents();
+        let (low, high) = (ren_to_world(&low), ren_to_world(&high));
+        // clear of the top row of cards, lined up with the left edge of the first column
+        let (left, top) = (low[0].min(high[0]) - 1.125, low[1].max(high[1]) + 2.25);
+        let meshes = asset_manager.read_assets::<Mesh>();
+        for (glyph, render, transform) in (&world.read::<status::Glyph>(), &mut world.write::<Renderable>(), &mut world.write::<LocalTransform>()).iter() {
+            let name = status::mesh_name(text.get(glyph.slot).cloned().unwrap_or(' '));
+            if let Some(mesh) = asset_manager.id_from_name(&name).and_then(|id| meshes.read(id)) {
+                render.mesh = mesh.clone();
+            }
+            transform.translation = [left + glyph.slot as f32 * status::ADVANCE, top, 0.5];
+        }
+    }
+    /// Settles up anything that depends on how the game ended.
+    fn finish(&mut self) {
+        self.session.finish(&self.opts);
+    }
+    fn hint(&mut self) {
+        if self.hints.is_empty() {
+            self.hints = game::hint::hints(self.session.history.current(), HINT_BUDGET);
+            self.hint = 0;
+        } else {
+            self.hint = (self.hint + 1) % self.hints.len();
+        }
+        match self.hints.get(self.hint) {
+            Some(act) => println!("hint {} of {}: {}", self.hint + 1, self.hints.len(), S::format_move(act)),
+            None => println!("no useful moves left")
+        }
+    }
+    fn solve(&mut self) {
+        let report = game::solver::solve(self.session.history.current(), self.opts.budget);
+        cmdline::print_report::<S>(&report);
+        if let game::solver::Outcome::Solved(acts) = report.outcome {
+            self.session.queue = acts.into_iter().collect();
+            self.wait = 0.0;
+        }
+    }
+    fn undo(&mut self, asset_manager: &mut AssetManager, world: &mut World) {
+        self.session.queue.clear();
+        self.drag = None;
+        if self.session.history.undo() {
+            self.refresh(asset_manager, world);
+        }
+    }
+    fn redo(&mut self, asset_manager: &mut AssetManager, world: &mut World) {
+        self.session.queue.clear();
+        self.drag = None;
+        if self.session.history.redo() {
+            self.refresh(asset_manager, world);
+        }
     }
 }
 
-impl State for Test {
+impl <S> State for Test<S> where S: Table + Problem<<S as Table>::Action, <S as Table>::Percept> + Search<<S as Table>::Action> {
     fn on_start(&mut self, world: &mut World, asset_manager: &mut AssetManager, pipe: &mut Pipeline) {
         use amethyst::renderer::pass::{Clear, DrawFlat};
         let layer = Layer::new("main",
@@ -349,8 +427,12 @@ impl State for Test {
         asset_manager.load_asset::<Texture>("amethyst_thumb", "png");
         asset_manager.load_asset::<Texture>("felt", "png");
 
-        let deck = cmdline::deck();
-        for crd in deck {
+        // one of each joker, for the games that use them
+        let deck = game::deal::Deck {
+            jokers: 2,
+            ..game::deal::Deck::standard()
+        };
+        for crd in deck.cards() {
             println!("{:?}", crd);
             asset_manager.load_asset::<Texture>(&get_card_asset_id(CardDisplay::Front(crd)), "png").unwrap();
         }
@@ -360,17 +442,26 @@ impl State for Test {
         asset_manager.load_asset_from_data::<Texture, [f32; 4]>("gray", [0.5, 0.5, 0.5, 1.0]);
         asset_manager.load_asset_from_data::<Mesh, Vec<VertexPosNormal>>("tri",isoc(1.0,1.0));
         asset_manager.load_asset_from_data::<Mesh, Vec<VertexPosNormal>>("tex10",tile_rect(8.0,10));
+        status::load(asset_manager);
         let tri = asset_manager.create_renderable("card", "cards/card_10_clubs", "white", "white", 1.0).unwrap();
         let plane = asset_manager.create_renderable("tex10", "felt", "white", "felt_green", 1.0).unwrap();
         //asset_manager.load_asset_from_data::<Texture, [f32; 4]>("white", [1.0, 1.0, 1.0, 1.0]);
         
-        let percept = self.state.percept();
+        let percept = self.session.history.current().percept();
         world.create_now()
             .with(plane.clone())
             .with(LocalTransform::default())
             .with(Transform::default())
             .with(Background)
             .build();
+        for slot in 0..status::SLOTS {
+            world.create_now()
+                .with(asset_manager.create_renderable("glyph_blank", "white", "white", "white", 1.0).unwrap())
+                .with(status::Glyph { slot })
+                .with(LocalTransform::default())
+                .with(Transform::default())
+                .build();
+        }
         for card in percept.get_cards() {
             let data = percept.get_data_for(card.clone()).unwrap();
                     world.create_now()
@@ -384,10 +475,25 @@ impl State for Test {
         }
     }
     fn update(&mut self, world: &mut World, asset_manager: &mut AssetManager, _: &mut Pipeline) -> Trans {
-        if self.state.is_goal() {
+        if self.session.history.current().is_goal() {
+            println!("deal #{} solved", self.opts.seed);
+            self.finish();
             return Trans::Quit;
         }
-        let percept = self.state.percept();
+        if !self.session.queue.is_empty() && !self.paused {
+            let delta = {
+                let time = world.read_resource::<amethyst::ecs::resources::Time>();
+                time.delta_time.as_secs() as f32 + time.delta_time.subsec_nanos() as f32 / 1000000000.0
+            };
+            self.wait -= delta;
+            if self.wait <= 0.0 {
+                self.wait = 0.3;
+                let act = self.session.queue.pop_front().unwrap();
+                self.do_thing(act, asset_manager, world);
+            }
+        }
+        self.draw_status(world, asset_manager);
+        let percept = self.session.history.current().percept();
         let card_list = percept.get_cards();
         let (cards, mut target, mut render) = (world.read::<CardThing>(), world.write::<springy::MoveTarget>(), world.write::<Renderable>());
         let textures = asset_manager.read_assets::<Texture>();
@@ -408,6 +514,11 @@ impl State for Test {
                 }
             }
         }
+        let highlight = match self.hints.get(self.hint).and_then(|act| percept.get_mouse_action_for(act)) {
+            Some(MouseAction::Drop(from, to)) => vec![from, to],
+            Some(MouseAction::Tap(card)) => vec![card],
+            None => Vec::new()
+        };
         for (card, target, render) in (&cards, &mut target, &mut render).iter() {
             if let Some(data) = percept.get_data_for(card.card.clone()) {
                 if dragging.contains(&card.card) {
@@ -416,6 +527,11 @@ impl State for Test {
                         pos[i] = pos[i] + drag_offset[i];
                     }
                     target.pos = pos;
+                } else if highlight.contains(&card.card) {
+                    let mut pos = ren_to_world(&data.pos);
+                    pos[1] += 0.4;
+                    pos[2] += 0.5;
+                    target.pos = pos;
                 } else {
                     target.pos = ren_to_world(&data.pos);
                 }
@@ -432,12 +548,33 @@ impl State for Test {
         for event in events {
             match event.payload {
                 Event::Closed | Event::KeyboardInput(_, _, Some(VirtualKeyCode::Escape)) => {
+                    self.finish();
                     return Trans::Quit;
                 },
                 Event::KeyboardInput(_, _, Some(VirtualKeyCode::Space)) => {
                     self.mush();
                     self.refresh(asset_manager, world);
                 },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::Z)) => {
+                    self.undo(asset_manager, world);
+                },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::Y)) => {
+                    self.redo(asset_manager, world);
+                },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::H)) => {
+                    self.hint();
+                },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::P)) => {
+                    self.paused = !self.paused;
+                },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::N)) => {
+                    if let Some(act) = self.session.queue.pop_front() {
+                        self.do_thing(act, asset_manager, world);
+                    }
+                },
+                Event::KeyboardInput(amethyst::ElementState::Pressed, _, Some(VirtualKeyCode::S)) => {
+                    self.solve();
+                },
                 Event::MouseMoved(x, y) => self.mouse_moved(x as f32, y as f32, world),
                 Event::MouseInput(_, _) => self.mouse_event(event.payload.clone(), asset_manager, world),
                 _ => ()
@@ -448,25 +585,81 @@ impl State for Test {
 }
 
 fn main(){
+    let mut opts = match cmdline::Options::from_args() {
+        Ok(opts) => opts,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", cmdline::usage());
+            std::process::exit(1);
+        }
+    };
+    if let Some(ref path) = opts.verify {
+        std::process::exit(if cmdline::cmd_verify(path) { 0 } else { 1 });
+    }
+    if let Some(count) = opts.bench {
+        cmdline::cmd_bench(&opts, count);
+        return;
+    }
+    if let Some(count) = opts.batch {
+        cmdline::cmd_batch(&opts, count);
+        return;
+    }
+    if opts.solve {
+        cmdline::cmd_solve(&opts);
+        return;
+    }
+    if opts.text {
+        if opts.replay.is_some() {
+            println!("replays play back in the window, use --verify to check one without it");
+            std::process::exit(1);
+        }
+        cmdline::cmd_solitare(&mut opts);
+        return;
+    }
+    match opts.game {
+        cmdline::Game::Klondike => play::<game::solitaire::Solitaire>(opts),
+        cmdline::Game::FreeCell => play::<game::freecell::FreeCell>(opts),
+        cmdline::Game::Spider => play::<game::spider::Spider>(opts),
+        cmdline::Game::Pyramid => play::<game::pyramid::Pyramid>(opts),
+        cmdline::Game::Golf | cmdline::Game::TriPeaks => play::<game::sequence::Sequence>(opts)
+    }
+}
+
+fn play<S>(mut opts: cmdline::Options) where S: Table + Problem<<S as Table>::Action, <S as Table>::Percept> + Search<<S as Table>::Action>, S::Percept: Send {
+    let session = match cmdline::Session::<S>::start(&mut opts) {
+        Ok(session) => session,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("{}", opts.describe());
     let resource_path = format!("{}/resources", env!("CARGO_MANIFEST_DIR"));
     let config_path = format!("{}/config.yml", resource_path);
-    let display_config = DisplayConfig::from_file(config_path).unwrap();
+    let mut display_config = DisplayConfig::from_file(config_path).unwrap();
+    display_config.title = format!("{} - {}", display_config.title, opts.describe());
     let initial = Test {
-        state: cmdline::deal_with_it(),
+        session,
         drag: None,
         spacing: [2.5, 4.5, 1.0],
         mouse: (0.0, 0.0),
         mouseray: raytrace::Ray {
             start: [0.0,0.0,0.0],
             velocity: [0.0,0.0,1.0]
-        }
+        },
+        paused: false,
+        wait: 0.0,
+        hints: Vec::new(),
+        hint: 0,
+        opts
     };
     let mut game = Application::build(initial, display_config)
         .register::<CardThing>()
         .register::<springy::MoveTarget>()
         .register::<Ident>()
         .register::<Background>()
-        .with::<CameraSystem<game::solitaire::CardGamePercept>>(CameraSystem(std::marker::PhantomData), "aspect", 10)
+        .register::<status::Glyph>()
+        .with::<CameraSystem<S::Percept>>(CameraSystem(std::marker::PhantomData), "aspect", 10)
         .with::<springy::MoveSystem>(springy::MoveSystem{vel:50.0}, "movement", 10)
         //.with(CardSystem { state: cmdline::deal_with_it() }, "cards", 1)
         .done();
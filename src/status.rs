@@ -0,0 +1,88 @@
+//! The score, move count and timer, drawn along the top of the window in a small block font.
+use amethyst::asset_manager::AssetManager;
+use amethyst::ecs::{
+    Component,
+    VecStorage,
+};
+use amethyst::ecs::components::Mesh;
+use amethyst::renderer::VertexPosNormal;
+
+/// How many characters of status can be shown.
+pub const SLOTS: usize = 48;
+/// The size of one pixel of a character, and the distance from one character to the next.
+pub const PIXEL: f32 = 0.15;
+pub const ADVANCE: f32 = 4.0 * PIXEL;
+
+/// Each character is three pixels wide and five high, written out a row at a time from the top.
+/// Letters only come in one case.
+const FONT: [(char, &str); 41] = [
+    ('0', "111101101101111"), ('1', "010110010010111"), ('2', "111001111100111"), ('3', "111001111001111"),
+    ('4', "101101111001001"), ('5', "111100111001111"), ('6', "111100111101111"), ('7', "111001001001001"),
+    ('8', "111101111101111"), ('9', "111101111001111"),
+    ('a', "010101111101101"), ('b', "110101110101110"), ('c', "011100100100011"), ('d', "110101101101110"),
+    ('e', "111100110100111"), ('f', "111100110100100"), ('g', "011100101101011"), ('h', "101101111101101"),
+    ('i', "111010010010111"), ('j', "001001001101010"), ('k', "101101110101101"), ('l', "100100100100111"),
+    ('m', "101111111101101"), ('n', "110101101101101"), ('o', "010101101101010"), ('p', "110101110100100"),
+    ('q', "010101101110011"), ('r', "110101110101101"), ('s', "011100010001110"), ('t', "111010010010010"),
+    ('u', "101101101101111"), ('v', "101101101101010"), ('w', "101101111111101"), ('x', "101101010101101"),
+    ('y', "101101010010010"), ('z', "111001010100111"),
+    (':', "000010000010000"), ('-', "000000111000000"), ('.', "000000000000010"), ('#', "101111101111101"),
+    ('$', "011110010011110")
+];
+
+/// One character of the status line, counting from the left.
+pub struct Glyph {
+    pub slot: usize
+}
+
+impl Component for Glyph {
+    type Storage = VecStorage<Glyph>;
+}
+
+/// The name of the mesh drawing `c`. Anything the font doesn't have is drawn as a space.
+pub fn mesh_name(c: char) -> String {
+    let c = c.to_ascii_lowercase();
+    if FONT.iter().any(|x| x.0 == c) {
+        format!("glyph_{}", c as u32)
+    } else {
+        String::from("glyph_blank")
+    }
+}
+
+/// Loads a mesh for every character in the font, and a blank one for spaces.
+pub fn load(asset_manager: &mut AssetManager) {
+    for &(c, pixels) in FONT.iter() {
+        asset_manager.load_asset_from_data::<Mesh, Vec<VertexPosNormal>>(&mesh_name(c), glyph(pixels));
+    }
+    asset_manager.load_asset_from_data::<Mesh, Vec<VertexPosNormal>>("glyph_blank", glyph(""));
+}
+
+/// Two triangles for every pixel that is set, with the bottom left corner of the character at
+/// the origin. A blank character is a single triangle with no area, so there is still something
+/// to draw.
+fn glyph(pixels: &str) -> Vec<VertexPosNormal> {
+    let vertex = |x: f32, y: f32| VertexPosNormal {
+        pos: [x, y, 0.0],
+        normal: [0.0, 0.0, 1.0],
+        tex_coord: [0.0, 0.0]
+    };
+    let mut out = Vec::new();
+    for (i, bit) in pixels.chars().enumerate() {
+        if bit != '1' {
+            continue;
+        }
+        let (x, y) = ((i % 3) as f32 * PIXEL, (4 - i / 3) as f32 * PIXEL);
+        out.push(vertex(x, y));
+        out.push(vertex(x + PIXEL, y));
+        out.push(vertex(x + PIXEL, y + PIXEL));
+        out.push(vertex(x, y));
+        out.push(vertex(x + PIXEL, y + PIXEL));
+        out.push(vertex(x, y + PIXEL));
+    }
+    if out.is_empty() {
+        for _ in 0..3 {
+            out.push(vertex(0.0, 0.0));
+        }
+    }
+    out
+}